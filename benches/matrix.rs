@@ -0,0 +1,64 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use aoc::matrix::{DynMatrix, Matrix};
+
+/// Counts the trees visible from outside the grid by walking all four directions from every tree,
+/// the access pattern of the day8 solution. Both layouts run the same loop and only differ in
+/// how a cell is indexed.
+fn visible_nested(grid: &[Vec<u8>]) -> usize {
+    let (size_x, size_y) = (grid.len(), grid[0].len());
+    let mut visible = 0;
+    for x in 0..size_x {
+        for y in 0..size_y {
+            let h = grid[x][y];
+            if (0..x).all(|i| grid[i][y] < h)
+                || (x + 1..size_x).all(|i| grid[i][y] < h)
+                || (0..y).all(|j| grid[x][j] < h)
+                || (y + 1..size_y).all(|j| grid[x][j] < h)
+            {
+                visible += 1;
+            }
+        }
+    }
+    visible
+}
+
+fn visible_flat(grid: &DynMatrix<u8>) -> usize {
+    let (size_x, size_y) = (grid.size_x(), grid.size_y());
+    let mut visible = 0;
+    for x in 0..size_x {
+        for y in 0..size_y {
+            let h = grid[(x, y)];
+            if (0..x).all(|i| grid[(i, y)] < h)
+                || (x + 1..size_x).all(|i| grid[(i, y)] < h)
+                || (0..y).all(|j| grid[(x, j)] < h)
+                || (y + 1..size_y).all(|j| grid[(x, j)] < h)
+            {
+                visible += 1;
+            }
+        }
+    }
+    visible
+}
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let input = std::fs::read_to_string("aoc2022/day8.txt").unwrap();
+    let nested: Vec<Vec<u8>> = input
+        .lines()
+        .map(|line| line.bytes().map(|b| b - b'0').collect())
+        .collect();
+    let flat = DynMatrix::from(nested.clone());
+    assert_eq!(visible_nested(&nested), visible_flat(&flat));
+
+    let mut group = c.benchmark_group("matrix_layout_day8");
+    group.bench_function("nested_vec", |b| {
+        b.iter(|| visible_nested(black_box(&nested)))
+    });
+    group.bench_function("flat_dynmatrix", |b| {
+        b.iter(|| visible_flat(black_box(&flat)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -109,3 +109,41 @@ pub async fn main(tc: TaskConfig) -> Result<()> {
     info!("Took {:.5} secs", time_taken.as_secs_f64());
     Ok(())
 }
+
+#[tracing::instrument]
+pub async fn run_command(command: &Command) -> Result<()> {
+    match command {
+        Command::Fs {
+            transcript,
+            host,
+            emit_transcript,
+            du,
+            max_depth,
+        } => {
+            let fs = if *host {
+                day7::FilesystemRef::from_host_dir(transcript).await?
+            } else {
+                day7::load_transcript(transcript).await?
+            };
+            day7::print_fs(&fs, *emit_transcript, *du, *max_depth)
+        }
+        Command::Debug { program } => day10::debugger::run_cli(program).await,
+        Command::Crt {
+            program,
+            output,
+            format,
+            scale,
+            frames_every,
+            width,
+            height,
+            sprite_width,
+        } => {
+            let geometry = day10::Geometry {
+                width: *width,
+                height: *height,
+                sprite_width: *sprite_width,
+            };
+            day10::export_screen(program, geometry, output, *format, *scale, *frames_every).await
+        }
+    }
+}
@@ -1,22 +1,22 @@
 use color_eyre::Report;
-use itertools::Itertools;
 use std::str::FromStr;
 
-use crate::*;
+use crate::{
+    image::{write_pbm, write_ppm, write_svg, ImageFormat, Rgb},
+    matrix::{DynMatrix, Matrix},
+    *,
+};
 
 use super::{Reportable, TaskResult};
 
+pub mod asm;
+pub mod debugger;
+pub mod ocr;
+pub mod trace;
+
 #[tracing::instrument]
 pub async fn part1() -> Result<Reportable> {
-    let mut input = read_file_lines("aoc2022/day10.txt").await?;
-
-    let mut instructions: Vec<Instruction> = Vec::new();
-
-    while let Some(line) = input.next().await {
-        instructions.push(line.parse()?);
-    }
-
-    instructions.push(Instruction::Stop);
+    let instructions = load_program("aoc2022/day10.txt").await?;
 
     let mut cpu_core = CPUCore {
         program: instructions,
@@ -24,111 +24,225 @@ pub async fn part1() -> Result<Reportable> {
     };
     cpu_core.reset();
 
-    let pause_cycles = [20, 60, 100, 140, 180, 220];
-
-    let mut pause_cycle_values = Vec::new();
-
-    while !cpu_core.stopped() {
-        cpu_core.cycle();
-        if pause_cycles.contains(&cpu_core.cycles) {
-            let signal_strength = cpu_core.registers.x * cpu_core.cycles as i128;
-            trace!("Signal strength added {signal_strength}");
-            pause_cycle_values.push(signal_strength);
-        }
-    }
+    let mut sampler = SignalSampler::new([20, 60, 100, 140, 180, 220]);
+    cpu_core.run_with(&mut sampler);
 
     Ok(Reportable {
         year: 2022,
         day: 10,
         part: 1.into(),
-        result: TaskResult::I128(pause_cycle_values.iter().sum()),
+        result: TaskResult::I128(sampler.total()),
     })
 }
 
 #[tracing::instrument]
 pub async fn part2() -> Result<Reportable> {
-    let mut input = read_file_lines("aoc2022/day10.txt").await?;
-
-    let mut instructions: Vec<Instruction> = Vec::new();
-
-    while let Some(line) = input.next().await {
-        instructions.push(line.parse()?);
-    }
-
-    instructions.push(Instruction::Stop);
+    let instructions = load_program("aoc2022/day10.txt").await?;
 
     let mut cpu_core = CPUCore {
         program: instructions,
         ..Default::default()
     };
     cpu_core.reset();
+    cpu_core.run_with(&mut |_: &CycleSnapshot| {});
 
-    while !cpu_core.stopped() {
-        cpu_core.cycle();
-    }
-
-    let output = cpu_core.crt.to_string();
-
-    trace!("Output:\n{output}");
+    info!("Screen:\n{}", cpu_core.crt);
 
     Ok(Reportable {
         year: 2022,
         day: 10,
         part: 2.into(),
-        result: TaskResult::String(output),
+        result: TaskResult::String(ocr::decode(&cpu_core.crt)?),
     })
 }
 
+/// Assembles a program file and terminates it with a [Instruction::Stop]
+pub async fn load_program(filename: &str) -> Result<Vec<Instruction>> {
+    let source: Vec<String> = read_file_lines(filename).await?.collect().await;
+    let mut instructions = asm::assemble(&source.join("\n")).context(filename.to_string())?;
+    instructions.push(Instruction::Stop);
+    check_program(&instructions).context(filename.to_string())?;
+    trace!("Program:\n{}", asm::disassemble(&instructions));
+    Ok(instructions)
+}
+
+/// Checks that a program can run on a [CPUCore] without leaving it:
+/// it ends in [Instruction::Stop] and jumps only to its own instructions
+pub fn check_program(program: &[Instruction]) -> Result<()> {
+    if program.last() != Some(&Instruction::Stop) {
+        return Err(report!("program must end with a stop instruction"));
+    }
+    if let Some((pc, target)) = program
+        .iter()
+        .enumerate()
+        .filter_map(|(pc, instr)| instr.jump_target().map(|target| (pc, target)))
+        .find(|(_, target)| *target >= program.len())
+    {
+        return Err(report!(
+            "instruction {pc} jumps to {target}, the program has {} instructions",
+            program.len()
+        ));
+    }
+    Ok(())
+}
+
+/// Runs a program on a screen of the given size and writes the final screen to `output`.
+/// With `frames_every` set, `output` is a directory that gets the screen after every
+/// that many cycles and at the end.
+pub async fn export_screen(
+    filename: &str,
+    geometry: Geometry,
+    output: &str,
+    format: ImageFormat,
+    scale: usize,
+    frames_every: Option<u128>,
+) -> Result<()> {
+    if scale == 0 {
+        return Err(report!("scale must be at least 1"));
+    }
+    let mut cpu_core = CPUCore {
+        program: load_program(filename).await?,
+        ..Default::default()
+    }
+    .with_geometry(geometry)?;
+    cpu_core.reset();
+    let Some(every) = frames_every else {
+        cpu_core.run_with(&mut |_: &CycleSnapshot| {});
+        let mut image = Vec::new();
+        cpu_core.crt.write_image(format, scale, &mut image)?;
+        return tokio::fs::write(output, image)
+            .await
+            .context(output.to_string());
+    };
+    if every == 0 {
+        return Err(report!("frames must be at least one cycle apart"));
+    }
+    let mut trace = trace::Trace::new(cpu_core.crt.geometry());
+    cpu_core.run_with(&mut trace);
+    tokio::fs::create_dir_all(output)
+        .await
+        .context(output.to_string())?;
+    for (cycle, screen) in trace.frames(every) {
+        let path =
+            std::path::Path::new(output).join(format!("frame_{cycle:04}.{}", format.extension()));
+        let mut image = Vec::new();
+        screen.write_image(format, scale, &mut image)?;
+        tokio::fs::write(&path, image)
+            .await
+            .with_context(|| path.display().to_string())?;
+    }
+    Ok(())
+}
+
+/// Colors of lit and dark pixels in PPM and SVG images
+pub const CRT_LIT: Rgb = Rgb(255, 176, 0);
+pub const CRT_DARK: Rgb = Rgb(24, 16, 8);
+
+/// Size of a [CRTScreen] and of the sprite `x` positions on it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Geometry {
+    pub width: usize,
+    pub height: usize,
+    /// Pixels covered by the sprite, centered on `x`; an even width extends one more to the right
+    pub sprite_width: usize,
+}
+
+impl Default for Geometry {
+    fn default() -> Self {
+        Self {
+            width: 40,
+            height: 6,
+            sprite_width: 3,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct CRTScreen {
-    display: [[bool; 40]; 6],
-    cur_line: u8,
-    cur_pixel: u8,
+    display: DynMatrix<bool>,
+    sprite_width: usize,
+    cur_line: usize,
+    cur_pixel: usize,
 }
 
 impl Default for CRTScreen {
     fn default() -> Self {
-        Self {
-            display: [[false; 40]; 6],
-            cur_line: Default::default(),
-            cur_pixel: Default::default(),
-        }
+        Self::new(Geometry::default()).expect("default geometry is valid")
     }
 }
 
 impl std::fmt::Display for CRTScreen {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let lines = self
-            .display
-            .iter()
-            .map(|line| {
-                line.iter()
-                    .map(|pixel| if *pixel { "#" } else { " " })
-                    .join("")
-            })
-            .join("\n");
+        let lines = self.display.render(|pixel| if *pixel { '#' } else { ' ' });
         f.write_str(&lines)
     }
 }
 
 impl CRTScreen {
+    /// A dark screen that starts drawing at its top left pixel
+    pub fn new(geometry: Geometry) -> Result<Self> {
+        let Geometry {
+            width,
+            height,
+            sprite_width,
+        } = geometry;
+        if width == 0 || height == 0 || sprite_width == 0 {
+            return Err(report!("invalid screen geometry {geometry:?}"));
+        }
+        Ok(Self {
+            display: DynMatrix::filled(height, width, false),
+            sprite_width,
+            cur_line: 0,
+            cur_pixel: 0,
+        })
+    }
+    pub fn geometry(&self) -> Geometry {
+        Geometry {
+            width: self.display.size_y(),
+            height: self.display.size_x(),
+            sprite_width: self.sprite_width,
+        }
+    }
+    /// Colors every pixel by whether it is lit
+    pub fn image(&self) -> DynMatrix<Rgb> {
+        DynMatrix::from_fn(self.display.size_x(), self.display.size_y(), |x, y| {
+            if self.display[(x, y)] {
+                CRT_LIT
+            } else {
+                CRT_DARK
+            }
+        })
+    }
+    /// Writes the screen with `scale` image pixels per pixel, lit pixels are black in PBM
+    pub fn write_image<W: std::io::Write>(
+        &self,
+        format: ImageFormat,
+        scale: usize,
+        out: &mut W,
+    ) -> Result<()> {
+        match format {
+            ImageFormat::Pbm => write_pbm(&self.display, scale, out).context("writing PBM screen"),
+            ImageFormat::Ppm => write_ppm(&self.image(), scale, out).context("writing PPM screen"),
+            ImageFormat::Svg => write_svg(&self.image(), scale, out).context("writing SVG screen"),
+        }
+    }
+    /// Whether the sprite at `sprite_pos` covers the pixel drawn next.
+    /// The sprite does not wrap to the neighbouring lines.
+    fn covers_current(&self, sprite_pos: i128) -> bool {
+        let width = self.sprite_width as i128;
+        let start = sprite_pos.saturating_sub((width - 1) / 2);
+        (start..start.saturating_add(width)).contains(&(self.cur_pixel as i128))
+    }
     fn draw_step(&mut self, registers: &Registers) {
-        let sprite_pos = registers.x;
-        // clamped register representation
-        let covered_pixels = (sprite_pos - 1, sprite_pos, sprite_pos + 1);
-        let cur_pixel = self.cur_pixel as i128;
-        let current_pixel_covered = cur_pixel == covered_pixels.0
-            || cur_pixel == covered_pixels.1
-            || cur_pixel == covered_pixels.2;
-        if current_pixel_covered {
-            self.display[self.cur_line as usize][self.cur_pixel as usize] = true;
+        if self.covers_current(registers.x) {
+            self.display[(self.cur_line, self.cur_pixel)] = true;
         }
         self.cur_pixel += 1;
-        if self.cur_pixel > 39 {
+        if self.cur_pixel == self.display.size_y() {
             self.cur_pixel = 0;
             self.cur_line += 1;
         }
-        if self.cur_line > 5 {
+        if self.cur_line == self.display.size_x() {
             self.cur_line = 0;
         }
     }
@@ -142,6 +256,7 @@ pub struct CPUCore {
     program: Vec<Instruction>,
     registers: Registers,
     crt: CRTScreen,
+    timing: Timing,
 }
 
 impl Default for CPUCore {
@@ -153,24 +268,53 @@ impl Default for CPUCore {
             program: Default::default(),
             registers: Default::default(),
             crt: CRTScreen::default(),
+            timing: Timing::default(),
         }
     }
 }
 
 impl CPUCore {
+    /// Replaces the cycle costs of the instructions, takes effect from the next instruction on
+    pub fn with_timing(mut self, timing: Timing) -> Self {
+        self.timing = timing;
+        self
+    }
+    /// Replaces the screen with a dark one of the given size
+    pub fn with_geometry(mut self, geometry: Geometry) -> Result<Self> {
+        self.crt = CRTScreen::new(geometry)?;
+        Ok(self)
+    }
+    pub fn screen(&self) -> &CRTScreen {
+        &self.crt
+    }
     pub fn reset(&mut self) {
         assert!(
             !self.program.is_empty(),
             "Attempted reset on CPU without program"
         );
         self.curr_instr = *self.program.first().unwrap();
-        self.cycles_on_instr_rem = self.curr_instr.delay();
+        self.cycles_on_instr_rem = self.timing.delay(self.curr_instr);
         self.registers = Registers::default();
     }
     pub fn stopped(&self) -> bool {
         self.curr_instr == Instruction::Stop
     }
+    /// State of the core during the cycle that runs next
+    pub fn snapshot(&self) -> CycleSnapshot {
+        CycleSnapshot {
+            cycle: self.cycles,
+            registers: self.registers,
+            instruction: self.curr_instr,
+            pixel: (self.crt.cur_line, self.crt.cur_pixel),
+            lit: self.crt.covers_current(self.registers.x),
+        }
+    }
     pub fn cycle(&mut self) {
+        self.cycle_with(&mut |_: &CycleSnapshot| {});
+    }
+    /// Runs one cycle, the observer sees the state during the cycle
+    pub fn cycle_with(&mut self, observer: &mut impl CycleObserver) {
+        observer.observe(&self.snapshot());
         self.cycles += 1;
         trace!("Advancing CPU to Cycle {}", self.cycles);
         self.cycles_on_instr_rem = self
@@ -183,13 +327,12 @@ impl CPUCore {
         if self.cycles_on_instr_rem == 0 {
             if self.curr_instr != Instruction::Stop {
                 self.curr_instr.apply_to_registers(&mut self.registers);
-                self.registers.pc += 1;
                 self.curr_instr = *self
                     .program
                     .get(self.registers.pc as usize)
                     .expect("program jumped outside execution limit");
             }
-            self.cycles_on_instr_rem = self.curr_instr.delay();
+            self.cycles_on_instr_rem = self.timing.delay(self.curr_instr);
             trace!(
                 "Next instruction: {:?}, {} cycles",
                 self.curr_instr,
@@ -197,17 +340,203 @@ impl CPUCore {
             );
         }
     }
+    /// Runs until the program stops, calling the observer during every cycle
+    pub fn run_with(&mut self, observer: &mut impl CycleObserver) {
+        while !self.stopped() {
+            self.cycle_with(observer);
+        }
+    }
+}
+
+/// State of the core during a cycle, before the instruction finishing in it takes effect
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CycleSnapshot {
+    pub cycle: u128,
+    pub registers: Registers,
+    /// Instruction executing during the cycle
+    pub instruction: Instruction,
+    /// Line and column the CRT draws during the cycle
+    pub pixel: (usize, usize),
+    /// Whether the CRT lights that pixel
+    pub lit: bool,
+}
+
+impl CycleSnapshot {
+    pub fn signal_strength(&self) -> i128 {
+        (self.cycle as i128).wrapping_mul(self.registers.x)
+    }
 }
 
-#[derive(Copy, Clone, Debug)]
+/// Gets called by [CPUCore::cycle_with] during every cycle
+pub trait CycleObserver {
+    fn observe(&mut self, snapshot: &CycleSnapshot);
+}
+
+impl<F: FnMut(&CycleSnapshot)> CycleObserver for F {
+    fn observe(&mut self, snapshot: &CycleSnapshot) {
+        self(snapshot)
+    }
+}
+
+/// Records the core state during the given cycles
+#[derive(Clone, Debug, Default)]
+pub struct SignalSampler {
+    cycles: Vec<u128>,
+    samples: Vec<CycleSnapshot>,
+}
+
+impl SignalSampler {
+    pub fn new(cycles: impl IntoIterator<Item = u128>) -> Self {
+        Self {
+            cycles: cycles.into_iter().collect(),
+            samples: Vec::new(),
+        }
+    }
+    pub fn samples(&self) -> &[CycleSnapshot] {
+        &self.samples
+    }
+    /// Sum of the signal strengths of all samples
+    pub fn total(&self) -> i128 {
+        self.samples
+            .iter()
+            .map(CycleSnapshot::signal_strength)
+            .sum()
+    }
+}
+
+impl CycleObserver for SignalSampler {
+    fn observe(&mut self, snapshot: &CycleSnapshot) {
+        if self.cycles.contains(&snapshot.cycle) {
+            trace!("Signal strength added {}", snapshot.signal_strength());
+            self.samples.push(*snapshot);
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Registers {
     x: i128,
+    a: i128,
+    b: i128,
+    c: i128,
     pc: usize,
 }
 
+impl Registers {
+    pub fn x(&self) -> i128 {
+        self.x
+    }
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+    pub fn get(&self, register: Register) -> i128 {
+        match register {
+            Register::X => self.x,
+            Register::A => self.a,
+            Register::B => self.b,
+            Register::C => self.c,
+        }
+    }
+    fn get_mut(&mut self, register: Register) -> &mut i128 {
+        match register {
+            Register::X => &mut self.x,
+            Register::A => &mut self.a,
+            Register::B => &mut self.b,
+            Register::C => &mut self.c,
+        }
+    }
+    fn value(&self, operand: Operand) -> i128 {
+        match operand {
+            Operand::Register(register) => self.get(register),
+            Operand::Immediate(v) => v,
+        }
+    }
+}
+
 impl Default for Registers {
     fn default() -> Self {
-        Self { x: 1, pc: 0 }
+        Self {
+            x: 1,
+            a: 0,
+            b: 0,
+            c: 0,
+            pc: 0,
+        }
+    }
+}
+
+/// General purpose register, `x` also positions the sprite on the [CRTScreen]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Register {
+    X,
+    A,
+    B,
+    C,
+}
+
+impl Register {
+    pub const ALL: [Register; 4] = [Register::X, Register::A, Register::B, Register::C];
+}
+
+impl FromStr for Register {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "x" => Register::X,
+            "a" => Register::A,
+            "b" => Register::B,
+            "c" => Register::C,
+            _ => return Err(report!("Invalid register {s:?}")),
+        })
+    }
+}
+
+impl std::fmt::Display for Register {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Register::X => "x",
+            Register::A => "a",
+            Register::B => "b",
+            Register::C => "c",
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Operand {
+    Register(Register),
+    Immediate(i128),
+}
+
+impl Operand {
+    fn address(self) -> Result<usize> {
+        match self {
+            Operand::Immediate(v) => v.try_into().context(report!("Invalid jump target {v}")),
+            Operand::Register(r) => Err(report!("Jump target must be an address, not {r}")),
+        }
+    }
+}
+
+impl FromStr for Operand {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(register) = s.parse() {
+            return Ok(Operand::Register(register));
+        }
+        s.parse()
+            .map(Operand::Immediate)
+            .context(report!("Invalid operand {s:?}"))
+    }
+}
+
+impl std::fmt::Display for Operand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operand::Register(r) => write!(f, "{r}"),
+            Operand::Immediate(v) => write!(f, "{v}"),
+        }
     }
 }
 
@@ -216,6 +545,15 @@ pub enum Instruction {
     #[default]
     Noop,
     AddX(i128),
+    /// Copies the operand into the register
+    Mov(Register, Operand),
+    Add(Register, Operand),
+    Sub(Register, Operand),
+    Mul(Register, Operand),
+    /// Continues at the given program index
+    Jmp(usize),
+    /// Continues at the given program index if the register is not zero
+    Jnz(Register, usize),
     Stop,
 }
 
@@ -224,47 +562,163 @@ impl FromStr for Instruction {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         if let Some((op, args)) = s.split_once(' ') {
-            Ok(match op {
-                "addx" => {
-                    let arg: i128 = args
-                        .parse()
-                        .context(report!("Invalid ADDX instruction parameter {s:?}"))?;
-                    Instruction::AddX(arg)
-                }
-                v => return Err(report!("Invalid PAR1 instruction {v:?}")),
-            })
+            let operands = args
+                .split(',')
+                .map(|arg| arg.trim().parse())
+                .collect::<Result<Vec<Operand>>>()
+                .context(report!("Invalid {op} instruction parameter {s:?}"))?;
+            Instruction::from_parts(op, &operands)
         } else {
-            Ok(match s {
-                "noop" => Instruction::Noop,
-                "stop" => Instruction::Stop,
-                v => return Err(report!("Invalid PAR0 instruction {v:?}")),
-            })
+            Instruction::from_parts(s, &[])
+        }
+    }
+}
+
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Instruction::Noop => write!(f, "noop"),
+            Instruction::AddX(v) => write!(f, "addx {v}"),
+            Instruction::Mov(r, v) => write!(f, "mov {r}, {v}"),
+            Instruction::Add(r, v) => write!(f, "add {r}, {v}"),
+            Instruction::Sub(r, v) => write!(f, "sub {r}, {v}"),
+            Instruction::Mul(r, v) => write!(f, "mul {r}, {v}"),
+            Instruction::Jmp(target) => write!(f, "jmp {target}"),
+            Instruction::Jnz(r, target) => write!(f, "jnz {r}, {target}"),
+            Instruction::Stop => write!(f, "stop"),
         }
     }
 }
 
 impl Instruction {
-    pub fn delay(self) -> u8 {
+    /// Builds an instruction from its mnemonic and the already resolved operands
+    pub fn from_parts(mnemonic: &str, operands: &[Operand]) -> Result<Self> {
+        use Operand::{Immediate, Register as Reg};
+        Ok(match (mnemonic, operands) {
+            ("noop", []) => Instruction::Noop,
+            ("stop", []) => Instruction::Stop,
+            ("addx", [Immediate(v)]) => Instruction::AddX(*v),
+            ("addx", []) => return Err(report!("Missing ADDX instruction parameter")),
+            ("mov", [Reg(r), v]) => Instruction::Mov(*r, *v),
+            ("add", [Reg(r), v]) => Instruction::Add(*r, *v),
+            ("sub", [Reg(r), v]) => Instruction::Sub(*r, *v),
+            ("mul", [Reg(r), v]) => Instruction::Mul(*r, *v),
+            ("jmp", [target]) => Instruction::Jmp(target.address()?),
+            ("jnz", [Reg(r), target]) => Instruction::Jnz(*r, target.address()?),
+            ("noop" | "stop", _) => return Err(report!("Invalid PAR1 instruction {mnemonic:?}")),
+            ("addx", _) => return Err(report!("Usage: addx VALUE")),
+            ("mov" | "add" | "sub" | "mul", _) => {
+                return Err(report!("Usage: {mnemonic} REGISTER, OPERAND"))
+            }
+            ("jmp", _) => return Err(report!("Usage: jmp TARGET")),
+            ("jnz", _) => return Err(report!("Usage: jnz REGISTER, TARGET")),
+            (v, _) => return Err(report!("Invalid instruction {v:?}")),
+        })
+    }
+
+    /// Program index a jump continues at
+    pub fn jump_target(self) -> Option<usize> {
         match self {
-            Instruction::Noop => 1,
-            Instruction::AddX(_) => 2,
-            Instruction::Stop => 1,
+            Instruction::Jmp(target) | Instruction::Jnz(_, target) => Some(target),
+            _ => None,
         }
     }
+
+    /// Cycles the instruction takes with the puzzle [Timing]
+    pub fn delay(self) -> u8 {
+        Timing::default().delay(self)
+    }
+    /// Executes the instruction and moves the program counter on to the next one.
+    /// Arithmetic wraps around on overflow like a two's complement register.
     pub fn apply_to_registers(self, regs: &mut Registers) {
+        regs.pc += 1;
         match self {
             Instruction::Noop => trace!("No operation"),
             Instruction::AddX(v) => {
                 trace!("Adding {v} to register X {}", regs.x);
-                regs.x += v;
+                regs.x = regs.x.wrapping_add(v);
+            }
+            Instruction::Mov(r, v) => *regs.get_mut(r) = regs.value(v),
+            Instruction::Add(r, v) => {
+                *regs.get_mut(r) = regs.get(r).wrapping_add(regs.value(v));
+            }
+            Instruction::Sub(r, v) => {
+                *regs.get_mut(r) = regs.get(r).wrapping_sub(regs.value(v));
+            }
+            Instruction::Mul(r, v) => {
+                *regs.get_mut(r) = regs.get(r).wrapping_mul(regs.value(v));
+            }
+            Instruction::Jmp(target) => regs.pc = target,
+            Instruction::Jnz(r, target) => {
+                if regs.get(r) != 0 {
+                    regs.pc = target;
+                }
             }
             Instruction::Stop => trace!("STOP INSTRUCTION"),
         }
     }
 }
 
+/// Cycle cost of each kind of instruction, the default is the one from the puzzle
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Timing {
+    pub noop: u8,
+    pub addx: u8,
+    pub mov: u8,
+    /// `add` and `sub`
+    pub arith: u8,
+    pub mul: u8,
+    /// `jmp` and `jnz`, whether taken or not
+    pub jump: u8,
+    pub stop: u8,
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Self {
+            noop: 1,
+            addx: 2,
+            mov: 1,
+            arith: 2,
+            mul: 4,
+            jump: 2,
+            stop: 1,
+        }
+    }
+}
+
+impl Timing {
+    /// Every instruction takes the same number of cycles
+    pub fn uniform(cycles: u8) -> Self {
+        Self {
+            noop: cycles,
+            addx: cycles,
+            mov: cycles,
+            arith: cycles,
+            mul: cycles,
+            jump: cycles,
+            stop: cycles,
+        }
+    }
+    /// Cycles the instruction takes, a cost of zero counts as one cycle
+    pub fn delay(&self, instr: Instruction) -> u8 {
+        let cycles = match instr {
+            Instruction::Noop => self.noop,
+            Instruction::AddX(_) => self.addx,
+            Instruction::Mov(..) => self.mov,
+            Instruction::Add(..) | Instruction::Sub(..) => self.arith,
+            Instruction::Mul(..) => self.mul,
+            Instruction::Jmp(_) | Instruction::Jnz(..) => self.jump,
+            Instruction::Stop => self.stop,
+        };
+        cycles.max(1)
+    }
+}
+
 #[cfg(test)]
 mod test {
+    use itertools::Itertools;
+
     use crate::aoc2022::day10::*;
 
     #[test]
@@ -303,54 +757,288 @@ mod test {
             ..Default::default()
         };
         cpu_core.reset();
-        while !cpu_core.stopped() {
+        let mut sampler = SignalSampler::new([20, 60, 100, 140, 180, 220]);
+        cpu_core.run_with(&mut sampler);
+
+        assert_eq!(
+            sampler
+                .samples()
+                .iter()
+                .map(|s| (s.cycle, s.registers.x))
+                .collect_vec(),
+            vec![
+                (20, 21),
+                (60, 19),
+                (100, 18),
+                (140, 21),
+                (180, 16),
+                (220, 18)
+            ]
+        );
+        assert_eq!(sampler.total(), 13140);
+    }
+
+    #[tokio::test]
+    async fn test_export_zero_scale() {
+        let err = export_screen(
+            "aoc2022/day10.txt",
+            Geometry::default(),
+            "unused.ppm",
+            ImageFormat::Ppm,
+            0,
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.to_string(), "scale must be at least 1");
+    }
+
+    #[test]
+    fn test_write_image() {
+        let mut screen = CRTScreen::default();
+        screen.display[(0, 0)] = true;
+        screen.display[(5, 39)] = true;
+
+        let mut pbm = Vec::new();
+        screen.write_image(ImageFormat::Pbm, 1, &mut pbm).unwrap();
+        let header = b"P4\n40 6\n";
+        assert_eq!(&pbm[..header.len()], header);
+        let pixels = &pbm[header.len()..];
+        assert_eq!(pixels.len(), 6 * 5);
+        assert_eq!((pixels[0], pixels[29]), (0b1000_0000, 0b0000_0001));
+        assert_eq!(pixels.iter().map(|b| b.count_ones()).sum::<u32>(), 2);
+
+        let mut ppm = Vec::new();
+        screen.write_image(ImageFormat::Ppm, 2, &mut ppm).unwrap();
+        assert!(ppm.starts_with(b"P6\n80 12\n255\n"));
+
+        let mut svg = Vec::new();
+        screen.write_image(ImageFormat::Svg, 3, &mut svg).unwrap();
+        let svg = String::from_utf8(svg).unwrap();
+        assert_eq!(svg.matches(&CRT_LIT.hex()).count(), 2);
+        assert_eq!(svg.matches(&CRT_DARK.hex()).count(), 238);
+    }
+
+    #[test]
+    fn test_geometry_wrap() {
+        let geometry = Geometry {
+            width: 5,
+            height: 2,
+            sprite_width: 1,
+        };
+        // x stays on column 4 after the first two cycles
+        let mut cpu_core = CPUCore {
+            program: vec![Instruction::AddX(3), Instruction::Noop, Instruction::Stop],
+            ..Default::default()
+        }
+        .with_geometry(geometry)
+        .unwrap();
+        cpu_core.reset();
+        let mut pixels = Vec::new();
+        for _ in 0..12 {
+            pixels.push(cpu_core.snapshot().pixel);
             cpu_core.cycle();
-            if cpu_core.cycles == 20 {
-                assert!(
-                    cpu_core.registers.x == 21,
-                    "Wanted X to be 21, got {}",
-                    cpu_core.registers.x
-                );
-            }
-            if cpu_core.cycles == 60 {
-                assert!(
-                    cpu_core.registers.x == 19,
-                    "Wanted X to be 19, got {}",
-                    cpu_core.registers.x
-                );
-            }
-            if cpu_core.cycles == 100 {
-                assert!(
-                    cpu_core.registers.x == 18,
-                    "Wanted X to be 18, got {}",
-                    cpu_core.registers.x
-                );
-            }
-            if cpu_core.cycles == 140 {
-                assert!(
-                    cpu_core.registers.x == 21,
-                    "Wanted X to be 21, got {}",
-                    cpu_core.registers.x
-                );
-            }
-            if cpu_core.cycles == 180 {
-                assert!(
-                    cpu_core.registers.x == 16,
-                    "Wanted X to be 16, got {}",
-                    cpu_core.registers.x
-                );
+        }
+        assert_eq!(
+            pixels,
+            vec![
+                (0, 0),
+                (0, 1),
+                (0, 2),
+                (0, 3),
+                (0, 4),
+                (1, 0),
+                (1, 1),
+                (1, 2),
+                (1, 3),
+                (1, 4),
+                (0, 0),
+                (0, 1)
+            ]
+        );
+        assert_eq!(cpu_core.screen().geometry(), geometry);
+        assert_eq!(cpu_core.crt.to_string(), " #  #\n    #");
+
+        assert!(CRTScreen::new(Geometry {
+            width: 0,
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_sprite_edges() {
+        let run = |geometry: Geometry, x: i128| {
+            let mut cpu_core = CPUCore {
+                program: vec![Instruction::AddX(x - 1), Instruction::Stop],
+                ..Default::default()
             }
-            if cpu_core.cycles == 220 {
-                assert!(
-                    cpu_core.registers.x == 18,
-                    "Wanted X to be 18, got {}",
-                    cpu_core.registers.x
-                );
+            .with_geometry(geometry)
+            .unwrap();
+            cpu_core.reset();
+            // move x into place before drawing the screen
+            cpu_core.cycle();
+            cpu_core.cycle();
+            cpu_core.crt = CRTScreen::new(geometry).unwrap();
+            for _ in 0..geometry.width * geometry.height {
+                cpu_core.crt.draw_step(&cpu_core.registers);
             }
+            cpu_core.crt.to_string()
+        };
+        let geometry = Geometry {
+            width: 8,
+            height: 2,
+            sprite_width: 3,
+        };
+        // the sprite is clipped at the edges instead of wrapping to the other side
+        assert_eq!(run(geometry, -1), "#       \n#       ");
+        assert_eq!(run(geometry, 8), "       #\n       #");
+        assert_eq!(run(geometry, 9), "        \n        ");
+        let wide = Geometry {
+            sprite_width: 4,
+            ..geometry
+        };
+        assert_eq!(run(wide, 3), "  ####  \n  ####  ");
+
+        // a larger display for stress programs
+        let large = Geometry {
+            width: 100,
+            height: 20,
+            sprite_width: 5,
+        };
+        let art = run(large, 50);
+        assert_eq!(art.lines().count(), 20);
+        assert!(art
+            .lines()
+            .all(|line| line.len() == 100 && line.find('#') == Some(48) && line.trim() == "#####"));
+    }
+
+    #[test]
+    fn test_snapshot_during_cycle() {
+        let mut cpu_core = CPUCore {
+            program: vec![Instruction::AddX(3), Instruction::Noop, Instruction::Stop],
+            ..Default::default()
+        };
+        cpu_core.reset();
+        let mut seen = Vec::new();
+        cpu_core.run_with(&mut |s: &CycleSnapshot| {
+            seen.push((s.cycle, s.registers.x, s.instruction, s.pixel.1))
+        });
+        assert_eq!(
+            seen,
+            vec![
+                (1, 1, Instruction::AddX(3), 0),
+                (2, 1, Instruction::AddX(3), 1),
+                (3, 4, Instruction::Noop, 2),
+            ]
+        );
+    }
+
+    pub(super) const FACTORIAL: &str = "\
+        mov a, 5
+        mov b, 1
+loop:   mul b, a
+        sub a, 1
+        jnz a, loop
+        mov x, b
+        jmp end
+        addx 1000
+end:    stop";
+
+    #[test]
+    fn test_extended_isa() {
+        let program = asm::assemble(FACTORIAL).unwrap();
+        let mut cpu_core = CPUCore {
+            program: program.clone(),
+            ..Default::default()
+        };
+        cpu_core.reset();
+        cpu_core.run_with(&mut |_: &CycleSnapshot| {});
+        assert_eq!(cpu_core.registers.x(), 120);
+        assert_eq!(cpu_core.registers.get(Register::A), 0);
+        assert_eq!(cpu_core.registers.pc(), 8);
+        assert_eq!(cpu_core.cycles, 1 + 2 + 5 * (4 + 2 + 2) + 1 + 2);
+
+        let mut cpu_core = CPUCore {
+            program,
+            ..Default::default()
+        }
+        .with_timing(Timing::uniform(1));
+        cpu_core.reset();
+        cpu_core.run_with(&mut |_: &CycleSnapshot| {});
+        assert_eq!(cpu_core.registers.x(), 120);
+        assert_eq!(cpu_core.cycles, 1 + 2 + 5 * 3 + 2);
+    }
+
+    #[test]
+    fn test_arithmetic_wraps() {
+        let mut regs = Registers::default();
+        Instruction::Mov(Register::A, Operand::Immediate(i128::MAX)).apply_to_registers(&mut regs);
+        Instruction::Add(Register::A, Operand::Immediate(1)).apply_to_registers(&mut regs);
+        assert_eq!(regs.get(Register::A), i128::MIN);
+        Instruction::Sub(Register::A, Operand::Immediate(1)).apply_to_registers(&mut regs);
+        assert_eq!(regs.get(Register::A), i128::MAX);
+        Instruction::Mul(Register::A, Operand::Register(Register::A)).apply_to_registers(&mut regs);
+        assert_eq!(regs.get(Register::A), 1);
+        Instruction::AddX(i128::MAX).apply_to_registers(&mut regs);
+        assert_eq!(regs.x(), i128::MIN);
+        // the sprite far off the screen must not overflow while drawing
+        let mut crt = CRTScreen::default();
+        crt.draw_step(&regs);
+        regs.x = i128::MAX;
+        crt.draw_step(&regs);
+        assert!(!crt.to_string().contains('#'));
+
+        // squaring over and over overflows quickly
+        let mut program = asm::assemble(
+            "mov a, 3
+             mov c, 200
+loop:        mul a, a
+             sub c, 1
+             jnz c, loop",
+        )
+        .unwrap();
+        program.push(Instruction::Stop);
+        let mut cpu_core = CPUCore {
+            program,
+            ..Default::default()
+        };
+        cpu_core.reset();
+        cpu_core.run_with(&mut |_: &CycleSnapshot| {});
+        assert_eq!(cpu_core.registers.pc(), 5);
+    }
+
+    #[test]
+    fn test_check_program() {
+        use Instruction::*;
+        assert!(check_program(&[Noop, Jmp(0), Stop]).is_ok());
+        assert!(check_program(&[Noop, Jmp(2), Stop]).is_ok());
+        assert!(check_program(&[Noop, Jmp(99), Stop]).is_err());
+        assert!(check_program(&[Jmp(0), Jmp(3), Stop]).is_err());
+        assert!(check_program(&[Jnz(Register::A, 3), Noop, Stop]).is_err());
+        assert!(check_program(&[Noop]).is_err());
+        assert!(check_program(&[]).is_err());
+    }
+
+    #[test]
+    fn test_parse_extended() {
+        for line in [
+            "mov a, -3",
+            "add x, b",
+            "sub c, 7",
+            "mul b, b",
+            "jmp 4",
+            "jnz a, 0",
+        ] {
+            assert_eq!(line.parse::<Instruction>().unwrap().to_string(), line);
         }
+        assert!("mov 3, a".parse::<Instruction>().is_err());
+        assert!("jmp a".parse::<Instruction>().is_err());
+        assert!("jnz a, -1".parse::<Instruction>().is_err());
+        assert!("add d, 1".parse::<Instruction>().is_err());
     }
 
-    const TEST_PROG_1: &str = r#"addx 15
+    pub(super) const TEST_PROG_1: &str = r#"addx 15
 addx -11
 addx 6
 addx -3
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+
+use crate::*;
+
+use super::{Instruction, Operand, Register};
+
+/// Assembles a program for [super::CPUCore].
+///
+/// Besides one instruction per line the source may contain
+/// - comments starting with `;` until the end of the line,
+/// - labels `name:` in front of an instruction or on their own line, which stand for
+///   the index of the next instruction,
+/// - constants `.const NAME = VALUE`, where the value may use earlier constants and labels.
+///
+/// Operands are separated by commas. Each is a register name or a decimal or `0x`
+/// hexadecimal number or symbol, optionally negated, so labels can be used as jump targets.
+/// Register names can't be used as symbols. Jump targets may point at most just past the
+/// last instruction, where [super::load_program] appends the [Instruction::Stop].
+/// No [Instruction::Stop] is appended.
+pub fn assemble(source: &str) -> Result<Vec<Instruction>> {
+    let mut symbols: HashMap<String, i128> = HashMap::new();
+    let mut pending: Vec<(usize, &str)> = Vec::new();
+
+    for (idx, line) in source.lines().enumerate() {
+        let line_no = idx + 1;
+        let mut line = line.split(';').next().unwrap_or_default().trim();
+        while let Some((label, rest)) = line.split_once(':') {
+            define(&mut symbols, label.trim(), pending.len() as i128)
+                .with_context(|| format!("line {line_no}"))?;
+            line = rest.trim();
+        }
+        if let Some(constant) = line.strip_prefix(".const ") {
+            let (name, value) = constant
+                .split_once('=')
+                .ok_or_else(|| report!("line {line_no}: expected `.const NAME = VALUE`"))?;
+            resolve(&symbols, value.trim())
+                .and_then(|value| define(&mut symbols, name.trim(), value))
+                .with_context(|| format!("line {line_no}"))?;
+        } else if !line.is_empty() {
+            pending.push((line_no, line));
+        }
+    }
+
+    let len = pending.len();
+    pending
+        .into_iter()
+        .map(|(line_no, line)| {
+            parse_instruction(&symbols, line)
+                .and_then(|instr| match instr.jump_target() {
+                    Some(target) if target > len => Err(report!(
+                        "jump target {target} is past the end of the program"
+                    )),
+                    _ => Ok(instr),
+                })
+                .with_context(|| format!("line {line_no}: {line}"))
+        })
+        .collect()
+}
+
+/// Lists a program with the program counter and the cycle each instruction starts on,
+/// assuming it runs straight through. The listing can be assembled again.
+pub fn disassemble(program: &[Instruction]) -> String {
+    let mut cycle = 1;
+    program
+        .iter()
+        .enumerate()
+        .map(|(pc, instr)| {
+            let line = format!("{:<12}; pc {pc:>4}, cycle {cycle}\n", instr.to_string());
+            cycle += instr.delay() as u128;
+            line
+        })
+        .collect()
+}
+
+fn is_ident(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn define(symbols: &mut HashMap<String, i128>, name: &str, value: i128) -> Result<()> {
+    if !is_ident(name) {
+        return Err(report!("invalid symbol name {name:?}"));
+    }
+    if name.parse::<Register>().is_ok() {
+        return Err(report!("symbol name {name} is a register"));
+    }
+    if symbols.insert(name.to_string(), value).is_some() {
+        return Err(report!("symbol {name} defined twice"));
+    }
+    Ok(())
+}
+
+fn resolve(symbols: &HashMap<String, i128>, operand: &str) -> Result<i128> {
+    if let Some(operand) = operand.strip_prefix('-') {
+        return resolve(symbols, operand).map(|value| -value);
+    }
+    if let Some(hex) = operand.strip_prefix("0x") {
+        return i128::from_str_radix(hex, 16).context(report!("invalid number {operand:?}"));
+    }
+    if operand.starts_with(|c: char| c.is_ascii_digit()) {
+        return operand
+            .parse()
+            .context(report!("invalid number {operand:?}"));
+    }
+    symbols
+        .get(operand)
+        .copied()
+        .ok_or_else(|| report!("undefined symbol {operand:?}"))
+}
+
+fn parse_instruction(symbols: &HashMap<String, i128>, line: &str) -> Result<Instruction> {
+    let (mnemonic, operands) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let operands = if operands.trim().is_empty() {
+        Vec::new()
+    } else {
+        operands
+            .split(',')
+            .map(|operand| {
+                let mut parts = operand.split_whitespace();
+                let operand = parts.next().ok_or_else(|| report!("missing operand"))?;
+                if let Some(extra) = parts.next() {
+                    return Err(report!("unexpected {extra:?} after operand"));
+                }
+                match operand.parse() {
+                    Ok(register) => Ok(Operand::Register(register)),
+                    Err(_) => resolve(symbols, operand).map(Operand::Immediate),
+                }
+            })
+            .collect::<Result<Vec<_>>>()?
+    };
+    Instruction::from_parts(&mnemonic.to_lowercase(), &operands)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_assemble() {
+        let program = assemble(
+            "; count up by STEP twice, then back down
+.const STEP = 3
+.const BACK = -STEP
+start:  noop
+        addx STEP   ; first
+loop:
+        ADDX 0x3
+        addx BACK
+        addx -loop
+end:    stop
+.const LEN = end",
+        )
+        .unwrap();
+        assert_eq!(
+            program,
+            vec![
+                Instruction::Noop,
+                Instruction::AddX(3),
+                Instruction::AddX(3),
+                Instruction::AddX(-3),
+                Instruction::AddX(-2),
+                Instruction::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        let err = |source: &str| format!("{:#}", assemble(source).unwrap_err());
+        assert_eq!(
+            err("noop\naddx FOO"),
+            "line 2: addx FOO: undefined symbol \"FOO\""
+        );
+        assert_eq!(err("l: noop\nl: noop"), "line 2: symbol l defined twice");
+        assert_eq!(err("x: noop"), "line 1: symbol name x is a register");
+        assert_eq!(err("mov a,, 1"), "line 1: mov a,, 1: missing operand");
+        assert_eq!(
+            err("noop\n\njump 4"),
+            "line 3: jump 4: Invalid instruction \"jump\""
+        );
+        assert_eq!(
+            err("addx 1 2"),
+            "line 1: addx 1 2: unexpected \"2\" after operand"
+        );
+        assert!(assemble(".const 1X = 2").is_err());
+        assert!(assemble(".const X 2").is_err());
+        assert!(assemble("noop 3").is_err());
+        assert_eq!(
+            err("noop\njmp 99"),
+            "line 2: jmp 99: jump target 99 is past the end of the program"
+        );
+        assert!(assemble("jmp end\nend:").is_ok());
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let program = vec![
+            Instruction::Noop,
+            Instruction::AddX(3),
+            Instruction::AddX(-5),
+            Instruction::Stop,
+        ];
+        let listing = disassemble(&program);
+        assert_eq!(
+            listing,
+            "noop        ; pc    0, cycle 1
+addx 3      ; pc    1, cycle 2
+addx -5     ; pc    2, cycle 4
+stop        ; pc    3, cycle 6
+"
+        );
+        assert_eq!(assemble(&listing).unwrap(), program);
+    }
+
+    #[test]
+    fn test_extended_isa() {
+        let program = assemble(super::super::test::FACTORIAL).unwrap();
+        assert_eq!(
+            program[2..5],
+            [
+                Instruction::Mul(Register::B, Operand::Register(Register::A)),
+                Instruction::Sub(Register::A, Operand::Immediate(1)),
+                Instruction::Jnz(Register::A, 2),
+            ]
+        );
+        assert_eq!(program[6], Instruction::Jmp(8));
+        assert_eq!(assemble(&disassemble(&program)).unwrap(), program);
+    }
+
+    #[test]
+    fn test_puzzle_program() {
+        let source = super::super::test::TEST_PROG_1;
+        let parsed: Vec<Instruction> = source.lines().map(|x| x.parse().unwrap()).collect();
+        assert_eq!(assemble(source).unwrap(), parsed);
+        assert_eq!(assemble(&disassemble(&parsed)).unwrap(), parsed);
+    }
+}
@@ -0,0 +1,447 @@
+use std::{fmt::Display, io::Write};
+
+use itertools::Itertools;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::*;
+
+use super::{asm, CPUCore, Instruction, Register};
+
+const HELP: &str = "\
+step [N]           advance N cycles (default 1)
+next [N]           advance N instructions (default 1)
+continue           run until a breakpoint is hit or the program stops
+run                run to completion, ignoring breakpoints
+break cycle N      stop once cycle N is reached
+break pc N         stop once the instruction at N is reached
+break REG OP N     stop once the condition becomes true, REG is x, a, b, c or pc,
+                   OP one of == != < <= > >=
+delete N           remove breakpoint N
+breakpoints        list breakpoints
+regs               show registers and the current instruction
+crt                show the screen
+list               disassemble around the current instruction
+reset              restart the program
+help               show this help
+quit               leave the debugger
+";
+
+/// Value a conditional breakpoint compares
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Watch {
+    Register(Register),
+    Pc,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    fn holds(self, lhs: i128, rhs: i128) -> bool {
+        match self {
+            Comparison::Eq => lhs == rhs,
+            Comparison::Ne => lhs != rhs,
+            Comparison::Lt => lhs < rhs,
+            Comparison::Le => lhs <= rhs,
+            Comparison::Gt => lhs > rhs,
+            Comparison::Ge => lhs >= rhs,
+        }
+    }
+}
+
+/// Condition the debugger stops on.
+///
+/// Breakpoints trigger when their condition changes from false to true,
+/// so continuing from a breakpoint does not stop at it again right away.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Breakpoint {
+    Cycle(u128),
+    Pc(usize),
+    Condition(Watch, Comparison, i128),
+}
+
+impl Breakpoint {
+    fn holds(&self, core: &CPUCore) -> bool {
+        match *self {
+            Breakpoint::Cycle(cycle) => core.cycles == cycle,
+            Breakpoint::Pc(pc) => core.registers.pc == pc,
+            Breakpoint::Condition(watch, cmp, value) => {
+                let lhs = match watch {
+                    Watch::Register(register) => core.registers.get(register),
+                    Watch::Pc => core.registers.pc as i128,
+                };
+                cmp.holds(lhs, value)
+            }
+        }
+    }
+}
+
+impl Display for Breakpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Breakpoint::Cycle(cycle) => write!(f, "cycle {cycle}"),
+            Breakpoint::Pc(pc) => write!(f, "pc {pc}"),
+            Breakpoint::Condition(watch, cmp, value) => {
+                let watch = match watch {
+                    Watch::Register(register) => register.to_string(),
+                    Watch::Pc => "pc".to_string(),
+                };
+                let cmp = match cmp {
+                    Comparison::Eq => "==",
+                    Comparison::Ne => "!=",
+                    Comparison::Lt => "<",
+                    Comparison::Le => "<=",
+                    Comparison::Gt => ">",
+                    Comparison::Ge => ">=",
+                };
+                write!(f, "{watch} {cmp} {value}")
+            }
+        }
+    }
+}
+
+/// Drives a [CPUCore] one command at a time, every command returns the text to show
+pub struct Debugger {
+    core: CPUCore,
+    breakpoints: Vec<Option<Breakpoint>>,
+}
+
+impl Debugger {
+    /// Loads the program, which must pass [super::check_program], and resets the core
+    pub fn new(program: Vec<Instruction>) -> Result<Self> {
+        super::check_program(&program)?;
+        let mut core = CPUCore {
+            program,
+            ..Default::default()
+        };
+        core.reset();
+        Ok(Self {
+            core,
+            breakpoints: Vec::new(),
+        })
+    }
+
+    pub fn core(&self) -> &CPUCore {
+        &self.core
+    }
+
+    /// Parses and runs a single debugger command
+    pub fn execute(&mut self, command: &str) -> Result<String> {
+        let words = command.split_whitespace().collect_vec();
+        let count = |idx: usize| -> Result<usize> {
+            let n = words.get(idx).map_or(Ok(1), |n| {
+                n.parse()
+                    .context(report!("invalid count {n:?}, see `help`"))
+            })?;
+            if n == 0 {
+                return Err(report!("count must be at least 1"));
+            }
+            Ok(n)
+        };
+        match words.first().copied().unwrap_or_default() {
+            "" => Ok(String::new()),
+            "s" | "step" => {
+                let n = count(1)?;
+                Ok(self.advance(n, |_, _| false))
+            }
+            "n" | "next" => {
+                let mut left = count(1)?;
+                let mut pc = self.core.registers.pc;
+                Ok(self.advance(usize::MAX, |core, _| {
+                    if core.registers.pc != pc {
+                        pc = core.registers.pc;
+                        left -= 1;
+                    }
+                    left == 0
+                }))
+            }
+            "c" | "continue" => Ok(self.advance(usize::MAX, |_, hit| hit.is_some())),
+            "run" => Ok(self.advance(usize::MAX, |_, _| false)),
+            "b" | "break" => {
+                let breakpoint = Self::parse_breakpoint(&words[1..])?;
+                self.breakpoints.push(Some(breakpoint));
+                Ok(format!(
+                    "breakpoint {}: {breakpoint}\n",
+                    self.breakpoints.len() - 1
+                ))
+            }
+            "d" | "delete" => {
+                let idx: usize = words
+                    .get(1)
+                    .ok_or_else(|| report!("delete needs a breakpoint number"))?
+                    .parse()?;
+                match self.breakpoints.get_mut(idx).and_then(Option::take) {
+                    Some(breakpoint) => Ok(format!("deleted breakpoint {idx}: {breakpoint}\n")),
+                    None => Err(report!("no breakpoint {idx}")),
+                }
+            }
+            "breakpoints" => Ok(self
+                .breakpoints
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, bp)| bp.map(|bp| format!("{idx}: {bp}\n")))
+                .collect()),
+            "r" | "regs" => Ok(self.status()),
+            "crt" => Ok(self.core.crt.to_string()),
+            "l" | "list" => Ok(self.listing()),
+            "reset" => {
+                self.core = CPUCore {
+                    program: std::mem::take(&mut self.core.program),
+                    ..Default::default()
+                };
+                self.core.reset();
+                Ok(self.status())
+            }
+            "h" | "help" => Ok(HELP.to_string()),
+            other => Err(report!("unknown command {other:?}, see `help`")),
+        }
+    }
+
+    /// Runs at most `cycles` cycles until `stop` returns true, which is given the
+    /// breakpoint that triggered on this cycle, if any.
+    fn advance(
+        &mut self,
+        cycles: usize,
+        mut stop: impl FnMut(&CPUCore, Option<usize>) -> bool,
+    ) -> String {
+        let mut out = String::new();
+        for _ in 0..cycles {
+            if self.core.stopped() {
+                break;
+            }
+            let before = self.active_breakpoints();
+            self.core.cycle();
+            let hit = self
+                .active_breakpoints()
+                .into_iter()
+                .find(|idx| !before.contains(idx));
+            if stop(&self.core, hit) {
+                if let Some(idx) = hit {
+                    let breakpoint = self.breakpoints[idx].expect("only active breakpoints hit");
+                    out.push_str(&format!("breakpoint {idx} hit: {breakpoint}\n"));
+                }
+                break;
+            }
+        }
+        if self.core.stopped() {
+            out.push_str("program stopped\n");
+        }
+        out.push_str(&self.status());
+        out
+    }
+
+    fn active_breakpoints(&self) -> Vec<usize> {
+        self.breakpoints
+            .iter()
+            .enumerate()
+            .filter(|(_, bp)| bp.is_some_and(|bp| bp.holds(&self.core)))
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    fn parse_breakpoint(words: &[&str]) -> Result<Breakpoint> {
+        let value = |word: &str| -> Result<i128> {
+            word.parse()
+                .context(report!("invalid breakpoint value {word:?}"))
+        };
+        Ok(match words {
+            ["cycle", n] => Breakpoint::Cycle(value(n)?.try_into()?),
+            ["pc", n] => Breakpoint::Pc(value(n)?.try_into()?),
+            [watch, cmp, n] => {
+                let watch = match *watch {
+                    "pc" => Watch::Pc,
+                    v => Watch::Register(v.parse().map_err(|_| report!("unknown register {v:?}"))?),
+                };
+                let cmp = match *cmp {
+                    "==" => Comparison::Eq,
+                    "!=" => Comparison::Ne,
+                    "<" => Comparison::Lt,
+                    "<=" => Comparison::Le,
+                    ">" => Comparison::Gt,
+                    ">=" => Comparison::Ge,
+                    v => return Err(report!("unknown comparison {v:?}")),
+                };
+                Breakpoint::Condition(watch, cmp, value(n)?)
+            }
+            _ => {
+                return Err(report!(
+                    "usage: break cycle N | break pc N | break REG OP N"
+                ))
+            }
+        })
+    }
+
+    /// Current cycle, registers and instruction, the registers besides `x` only once they are used
+    fn status(&self) -> String {
+        let core = &self.core;
+        let registers: String = Register::ALL
+            .into_iter()
+            .filter(|&r| r == Register::X || core.registers.get(r) != 0)
+            .map(|r| format!("{r} = {} | ", core.registers.get(r)))
+            .collect();
+        format!(
+            "cycle {} | {registers}pc = {} | {} ({} cycles left)\n",
+            core.cycles, core.registers.pc, core.curr_instr, core.cycles_on_instr_rem
+        )
+    }
+
+    fn listing(&self) -> String {
+        let pc = self.core.registers.pc;
+        asm::disassemble(&self.core.program)
+            .lines()
+            .enumerate()
+            .skip(pc.saturating_sub(5))
+            .take(11)
+            .map(|(idx, line)| {
+                let marker = if idx == pc { "=>" } else { "  " };
+                format!("{marker} {line}\n")
+            })
+            .collect()
+    }
+}
+
+/// Runs the debugger on a program file, reading commands from stdin until `quit` or EOF
+pub async fn run_cli(filename: &str) -> Result<()> {
+    if filename == STDIN_INPUT {
+        return Err(report!(
+            "the debugger reads its commands from stdin, load the program from a file"
+        ));
+    }
+    let mut debugger = Debugger::new(super::load_program(filename).await?)?;
+    println!("{}", debugger.status().trim_end());
+    let mut commands = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        print!("(dbg) ");
+        std::io::stdout().flush()?;
+        let Some(command) = commands.next_line().await? else {
+            break;
+        };
+        if matches!(command.trim(), "q" | "quit") {
+            break;
+        }
+        match debugger.execute(&command) {
+            Ok(output) => print!("{output}"),
+            Err(err) => println!("error: {err:#}"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn debugger() -> Debugger {
+        let mut program = asm::assemble(super::super::test::TEST_PROG_1).unwrap();
+        program.push(Instruction::Stop);
+        Debugger::new(program).unwrap()
+    }
+
+    #[test]
+    fn test_step() {
+        let mut dbg = debugger();
+        assert_eq!(
+            dbg.execute("regs").unwrap(),
+            "cycle 1 | x = 1 | pc = 0 | addx 15 (2 cycles left)\n"
+        );
+        assert_eq!(
+            dbg.execute("step").unwrap(),
+            "cycle 2 | x = 1 | pc = 0 | addx 15 (1 cycles left)\n"
+        );
+        assert_eq!(
+            dbg.execute("next 2").unwrap(),
+            "cycle 5 | x = 5 | pc = 2 | addx 6 (2 cycles left)\n"
+        );
+        assert_eq!(
+            dbg.execute("step 15").unwrap(),
+            "cycle 20 | x = 21 | pc = 10 | addx -1 (2 cycles left)\n"
+        );
+        assert!(dbg
+            .execute("list")
+            .unwrap()
+            .contains("=> addx -1     ; pc   10, cycle 20\n"));
+        assert_eq!(
+            dbg.execute("reset").unwrap(),
+            "cycle 1 | x = 1 | pc = 0 | addx 15 (2 cycles left)\n"
+        );
+    }
+
+    #[test]
+    fn test_breakpoint() {
+        let mut dbg = debugger();
+        assert_eq!(
+            dbg.execute("break cycle 60").unwrap(),
+            "breakpoint 0: cycle 60\n"
+        );
+        dbg.execute("break x > 20").unwrap();
+        dbg.execute("b pc 100").unwrap();
+        assert_eq!(
+            dbg.execute("continue").unwrap(),
+            "breakpoint 1 hit: x > 20\ncycle 19 | x = 21 | pc = 9 | noop (1 cycles left)\n"
+        );
+        assert_eq!(
+            dbg.execute("c").unwrap(),
+            "breakpoint 1 hit: x > 20\ncycle 24 | x = 25 | pc = 12 | addx -1 (2 cycles left)\n"
+        );
+        dbg.execute("delete 1").unwrap();
+        assert_eq!(
+            dbg.execute("breakpoints").unwrap(),
+            "0: cycle 60\n2: pc 100\n"
+        );
+        assert_eq!(
+            dbg.execute("c").unwrap(),
+            "breakpoint 0 hit: cycle 60\ncycle 60 | x = 19 | pc = 32 | addx -3 (2 cycles left)\n"
+        );
+        assert!(dbg
+            .execute("c")
+            .unwrap()
+            .starts_with("breakpoint 2 hit: pc 100\n"));
+        assert_eq!(dbg.core().registers.pc, 100);
+
+        assert_eq!(
+            dbg.execute("run").unwrap(),
+            "program stopped\ncycle 241 | x = 17 | pc = 146 | stop (1 cycles left)\n"
+        );
+        assert_eq!(
+            dbg.execute("crt").unwrap().lines().next(),
+            Some("##  ##  ##  ##  ##  ##  ##  ##  ##  ##  ")
+        );
+        assert!(dbg.execute("step").unwrap().starts_with("program stopped"));
+
+        assert!(dbg.execute("break x ~ 3").is_err());
+        assert!(dbg.execute("break d == 3").is_err());
+        assert!(dbg.execute("break cycle -1").is_err());
+        assert!(dbg.execute("delete 1").is_err());
+        assert!(dbg.execute("jump").is_err());
+        assert!(dbg.execute("step 0").is_err());
+        assert!(dbg.execute("next 0").is_err());
+        assert!(dbg.execute("next x").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stdin_program() {
+        assert!(run_cli(STDIN_INPUT).await.is_err());
+    }
+
+    #[test]
+    fn test_extended_registers() {
+        let mut program = asm::assemble(super::super::test::FACTORIAL).unwrap();
+        program.push(Instruction::Stop);
+        let mut dbg = Debugger::new(program).unwrap();
+        assert_eq!(
+            dbg.execute("break b >= 20").unwrap(),
+            "breakpoint 0: b >= 20\n"
+        );
+        assert_eq!(
+            dbg.execute("c").unwrap(),
+            "breakpoint 0 hit: b >= 20\ncycle 15 | x = 1 | a = 4 | b = 20 | pc = 3 | sub a, 1 (2 cycles left)\n"
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use itertools::Itertools;
+
+use crate::{matrix::Matrix, *};
+
+use super::CRTScreen;
+
+/// Glyphs are drawn 6 pixels high, every letter starts 5 columns after the previous one
+pub const GLYPH_HEIGHT: usize = 6;
+pub const GLYPH_PITCH: usize = 5;
+
+/// The capital letters the puzzles draw. Most are 4 pixels wide, the column after
+/// them is blank unless the letter is wider.
+const FONT: [(char, [&str; GLYPH_HEIGHT]); 18] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Y', ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#.."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+/// Pixels of a glyph cell as rows of `#` and `.`, padded to the full pitch
+fn cell_pattern(pixel: impl Fn(usize, usize) -> bool) -> Vec<String> {
+    (0..GLYPH_HEIGHT)
+        .map(|line| {
+            (0..GLYPH_PITCH)
+                .map(|col| if pixel(line, col) { '#' } else { '.' })
+                .collect()
+        })
+        .collect()
+}
+
+fn lookup(pattern: &[String]) -> Option<char> {
+    FONT.iter()
+        .find(|(_, glyph)| {
+            glyph
+                .iter()
+                .zip(pattern)
+                .all(|(row, cell)| format!("{row:.<GLYPH_PITCH$}") == *cell)
+        })
+        .map(|(letter, _)| *letter)
+}
+
+/// Reads the letters drawn on the screen, one per glyph cell from the left.
+/// Blank cells read as spaces.
+///
+/// Cells that don't match a letter of the font are reported with their
+/// position and the pixels found there.
+pub fn decode(screen: &CRTScreen) -> Result<String> {
+    let display = &screen.display;
+    if display.size_x() != GLYPH_HEIGHT {
+        return Err(report!(
+            "screen is {} lines high, letters are {GLYPH_HEIGHT}",
+            display.size_x()
+        ));
+    }
+    (0..display.size_y().div_ceil(GLYPH_PITCH))
+        .map(|cell| {
+            let start = cell * GLYPH_PITCH;
+            let pattern = cell_pattern(|line, col| {
+                display.get(line, start + col).copied().unwrap_or_default()
+            });
+            if pattern.iter().all(|row| !row.contains('#')) {
+                return Ok(' ');
+            }
+            lookup(&pattern).ok_or_else(|| {
+                report!(
+                    "unknown glyph {} at columns {start}..{}:\n{}",
+                    cell + 1,
+                    start + GLYPH_PITCH,
+                    pattern.iter().join("\n")
+                )
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn screen(art: &[&str]) -> CRTScreen {
+        let mut screen = CRTScreen::default();
+        for (line, row) in art.iter().enumerate() {
+            for (col, pixel) in row.chars().enumerate() {
+                screen.display[(line, col)] = pixel == '#';
+            }
+        }
+        screen
+    }
+
+    /// Draws the letters with the font, one per cell
+    fn write(letters: &str) -> CRTScreen {
+        let mut screen = CRTScreen::default();
+        for (cell, letter) in letters.chars().enumerate().filter(|(_, l)| *l != ' ') {
+            let (_, glyph) = FONT.iter().find(|(l, _)| *l == letter).unwrap();
+            for (line, row) in glyph.iter().enumerate() {
+                for (col, pixel) in row.chars().enumerate() {
+                    screen.display[(line, cell * GLYPH_PITCH + col)] = pixel == '#';
+                }
+            }
+        }
+        screen
+    }
+
+    #[test]
+    fn test_decode() {
+        for letters in ["ABCEFGHI", "JKLOPRSU", "YZ  AZ", ""] {
+            assert_eq!(
+                decode(&write(letters)).unwrap(),
+                format!("{letters:<8}"),
+                "{letters}"
+            );
+        }
+
+        // drawn by the puzzle input
+        let screen = screen(&[
+            "####.#..#..##..###..#..#..##..###..#..#.",
+            "...#.#.#..#..#.#..#.#.#..#..#.#..#.#.#..",
+            "..#..##...#....#..#.##...#....#..#.##...",
+            ".#...#.#..#.##.###..#.#..#.##.###..#.#..",
+            "#....#.#..#..#.#.#..#.#..#..#.#.#..#.#..",
+            "####.#..#..###.#..#.#..#..###.#..#.#..#.",
+        ]);
+        assert_eq!(decode(&screen).unwrap(), "ZKGRKGRK");
+    }
+
+    #[test]
+    fn test_unknown_glyph() {
+        let mut screen = write("AB");
+        screen.display[(5, 7)] = false;
+        let err = decode(&screen).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown glyph 2 at columns 5..10:
+###..
+#..#.
+###..
+#..#.
+#..#.
+##..."
+        );
+    }
+}
@@ -0,0 +1,333 @@
+use std::fmt::Write;
+
+use itertools::Itertools;
+
+use crate::{
+    matrix::{DynMatrix, Matrix},
+    *,
+};
+
+use super::{CRTScreen, CycleObserver, CycleSnapshot, Geometry, Instruction};
+
+/// Largest number of values [Trace::plot_x] draws rows for
+pub const MAX_PLOT_ROWS: usize = 1000;
+
+/// What happened during a single cycle
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TraceEntry {
+    pub cycle: u128,
+    pub pc: usize,
+    #[serde(with = "instruction_text")]
+    pub instruction: Instruction,
+    pub x: i128,
+    pub line: usize,
+    pub column: usize,
+    /// Whether the CRT lit the pixel at `line` and `column`
+    pub lit: bool,
+}
+
+impl From<&CycleSnapshot> for TraceEntry {
+    fn from(snapshot: &CycleSnapshot) -> Self {
+        Self {
+            cycle: snapshot.cycle,
+            pc: snapshot.registers.pc(),
+            instruction: snapshot.instruction,
+            x: snapshot.registers.x(),
+            line: snapshot.pixel.0,
+            column: snapshot.pixel.1,
+            lit: snapshot.lit,
+        }
+    }
+}
+
+/// Instructions are stored as their assembly text
+mod instruction_text {
+    use super::Instruction;
+
+    pub fn serialize<S: serde::Serializer>(instr: &Instruction, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(instr)
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Instruction, D::Error> {
+        let text: String = serde::Deserialize::deserialize(d)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Per-cycle record of a run, recorded by passing it to [super::CPUCore::run_with].
+///
+/// The trace can be exported as CSV or JSON, loaded back from JSON and replayed
+/// to the screen or the `x` register at any recorded cycle.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Trace {
+    /// Screen the run drew on, replays draw on one of the same size
+    geometry: Geometry,
+    entries: Vec<TraceEntry>,
+}
+
+impl CycleObserver for Trace {
+    fn observe(&mut self, snapshot: &CycleSnapshot) {
+        self.entries.push(snapshot.into());
+    }
+}
+
+impl Trace {
+    /// An empty trace for a core whose screen has the given geometry
+    pub fn new(geometry: Geometry) -> Self {
+        Self {
+            geometry,
+            entries: Vec::new(),
+        }
+    }
+    pub fn geometry(&self) -> Geometry {
+        self.geometry
+    }
+    pub fn entries(&self) -> &[TraceEntry] {
+        &self.entries
+    }
+
+    /// The entry recorded during the given cycle
+    pub fn at(&self, cycle: u128) -> Option<&TraceEntry> {
+        self.entries
+            .binary_search_by_key(&cycle, |entry| entry.cycle)
+            .ok()
+            .map(|idx| &self.entries[idx])
+    }
+
+    /// One line per cycle after a header, the instruction is always quoted
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("cycle,pc,instruction,x,line,column,lit\n");
+        for e in &self.entries {
+            writeln!(
+                out,
+                "{},{},\"{}\",{},{},{},{}",
+                e.cycle, e.pc, e.instruction, e.x, e.line, e.column, e.lit
+            )
+            .expect("writing to a string can't fail");
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        let trace: Trace = serde_json::from_str(json).context("invalid trace")?;
+        if !trace
+            .entries
+            .iter()
+            .tuple_windows()
+            .all(|(a, b)| a.cycle < b.cycle)
+        {
+            return Err(report!("trace cycles are not increasing"));
+        }
+        let screen = trace.blank_screen()?;
+        if let Some(e) = trace
+            .entries
+            .iter()
+            .find(|e| screen.display.get(e.line, e.column).is_none())
+        {
+            return Err(report!(
+                "cycle {} draws pixel {},{} outside of the {}x{} screen",
+                e.cycle,
+                e.line,
+                e.column,
+                trace.geometry.width,
+                trace.geometry.height
+            ));
+        }
+        Ok(trace)
+    }
+
+    fn blank_screen(&self) -> Result<CRTScreen> {
+        CRTScreen::new(self.geometry)
+    }
+
+    /// The screen as it looks once the given cycle has been drawn
+    pub fn screen_at(&self, cycle: u128) -> CRTScreen {
+        let mut screen = self.blank_screen().expect("trace geometry is valid");
+        for e in self.entries.iter().take_while(|e| e.cycle <= cycle) {
+            if e.lit {
+                screen.display[(e.line, e.column)] = true;
+            }
+        }
+        screen
+    }
+
+    /// The screen after every `every` cycles and after the last recorded cycle,
+    /// together with the cycle it shows
+    pub fn frames(&self, every: u128) -> Vec<(u128, CRTScreen)> {
+        assert!(every > 0, "frames must be at least one cycle apart");
+        let mut screen = self.blank_screen().expect("trace geometry is valid");
+        let mut frames = Vec::new();
+        for e in &self.entries {
+            if e.lit {
+                screen.display[(e.line, e.column)] = true;
+            }
+            if e.cycle % every == 0 {
+                frames.push((e.cycle, screen.clone()));
+            }
+        }
+        if let Some(last) = self.entries.last() {
+            if last.cycle % every != 0 {
+                frames.push((last.cycle, screen));
+            }
+        }
+        frames
+    }
+
+    /// Plots `x` during every recorded cycle up to the given one, one column per cycle
+    /// and one row per value with the largest on top. Fails if that would take more
+    /// than [MAX_PLOT_ROWS] rows.
+    pub fn plot_x(&self, until: u128) -> Result<String> {
+        let values = self
+            .entries
+            .iter()
+            .take_while(|e| e.cycle <= until)
+            .map(|e| e.x)
+            .collect_vec();
+        let Some((min, max)) = values.iter().copied().minmax().into_option() else {
+            return Ok(String::new());
+        };
+        let rows = max
+            .checked_sub(min)
+            .and_then(|span| usize::try_from(span).ok())
+            .filter(|span| *span < MAX_PLOT_ROWS)
+            .ok_or_else(|| {
+                report!("x spans {min}..={max}, more than {MAX_PLOT_ROWS} rows to plot")
+            })?
+            + 1;
+        let plot = DynMatrix::from_fn(rows, values.len(), |row, col| {
+            values[col] == max - row as i128
+        });
+        Ok(plot
+            .render(|lit| if *lit { '*' } else { ' ' })
+            .lines()
+            .enumerate()
+            .map(|(row, line)| format!("{:>4} |{}\n", max - row as i128, line.trim_end()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::aoc2022::day10::{asm, test::TEST_PROG_1, CPUCore};
+
+    fn record(source: &str) -> (CPUCore, Trace) {
+        let mut program = asm::assemble(source).unwrap();
+        program.push(Instruction::Stop);
+        let mut core = CPUCore {
+            program,
+            ..Default::default()
+        };
+        core.reset();
+        let mut trace = Trace::new(core.screen().geometry());
+        core.run_with(&mut trace);
+        (core, trace)
+    }
+
+    #[test]
+    fn test_replay() {
+        let (core, trace) = record(TEST_PROG_1);
+        assert_eq!(trace.entries().len(), 240);
+        assert_eq!(trace.screen_at(240).to_string(), core.crt.to_string());
+
+        let partial = trace.screen_at(42).to_string();
+        let lines = partial.lines().collect_vec();
+        assert_eq!(lines[0], core.crt.to_string().lines().next().unwrap());
+        assert_eq!(lines[1].trim_end(), "##");
+        assert!(lines[2..].iter().all(|line| line.trim().is_empty()));
+
+        let during_20 = trace.at(20).unwrap();
+        assert_eq!((during_20.x, during_20.pc, during_20.column), (21, 10, 19));
+        assert_eq!(trace.at(241), None);
+    }
+
+    #[test]
+    fn test_frames() {
+        let (core, trace) = record(TEST_PROG_1);
+        let frames = trace.frames(100);
+        assert_eq!(
+            frames.iter().map(|(cycle, _)| *cycle).collect_vec(),
+            vec![100, 200, 240]
+        );
+        assert_eq!(frames[0].1.to_string(), trace.screen_at(100).to_string());
+        assert_eq!(frames[2].1.to_string(), core.crt.to_string());
+        assert_eq!(trace.frames(240).len(), 1);
+        assert!(Trace::default().frames(1).is_empty());
+    }
+
+    #[test]
+    fn test_export() {
+        let (_, trace) = record("addx 2\nmov a, 3\naddx -1");
+        assert_eq!(
+            trace.to_csv(),
+            "cycle,pc,instruction,x,line,column,lit
+1,0,\"addx 2\",1,0,0,true
+2,0,\"addx 2\",1,0,1,true
+3,1,\"mov a, 3\",3,0,2,true
+4,2,\"addx -1\",3,0,3,true
+5,2,\"addx -1\",3,0,4,true
+"
+        );
+        let json = trace.to_json().unwrap();
+        assert!(json.contains("\"instruction\": \"mov a, 3\""));
+        assert_eq!(Trace::from_json(&json).unwrap(), trace);
+        assert!(Trace::from_json("[{\"cycle\": 1}]").is_err());
+        let outside = json.replace("\"column\": 4", "\"column\": 40");
+        assert_eq!(
+            Trace::from_json(&outside).unwrap_err().to_string(),
+            "cycle 5 draws pixel 0,40 outside of the 40x6 screen"
+        );
+    }
+
+    #[test]
+    fn test_geometry() {
+        let geometry = Geometry {
+            width: 4,
+            height: 2,
+            sprite_width: 1,
+        };
+        let mut program = asm::assemble("addx 1\naddx 1\nnoop\nnoop\naddx -3\nnoop").unwrap();
+        program.push(Instruction::Stop);
+        let mut core = CPUCore {
+            program,
+            ..Default::default()
+        }
+        .with_geometry(geometry)
+        .unwrap();
+        core.reset();
+        let mut trace = Trace::new(geometry);
+        core.run_with(&mut trace);
+        assert_eq!(trace.screen_at(8).to_string(), " ## \n   #");
+        // the ninth cycle wraps around to the top left pixel
+        assert_eq!(trace.screen_at(9).to_string(), core.screen().to_string());
+        assert_eq!(trace.screen_at(9).to_string(), "### \n   #");
+        assert_eq!(trace.screen_at(9).geometry(), geometry);
+        assert_eq!(Trace::from_json(&trace.to_json().unwrap()).unwrap(), trace);
+    }
+
+    #[test]
+    fn test_plot() {
+        let (_, trace) = record("addx 2\nnoop\naddx -3\nnoop");
+        assert_eq!(
+            trace.plot_x(6).unwrap(),
+            "   3 |  ***
+   2 |
+   1 |**
+   0 |     *
+"
+        );
+        assert_eq!(trace.plot_x(0).unwrap(), "");
+
+        let (_, trace) = record("mov x, 1000000\nnoop");
+        assert_eq!(
+            trace.plot_x(3).unwrap_err().to_string(),
+            "x spans 1..=1000000, more than 1000 rows to plot"
+        );
+        let (_, trace) = record("mov x, -0x7fffffffffffffffffffffffffffffff\nmov x, 0x7fffffffffffffffffffffffffffffff\nnoop");
+        assert!(trace.plot_x(4).is_err());
+    }
+}
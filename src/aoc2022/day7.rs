@@ -1,40 +1,26 @@
-use std::{
-    cell::RefCell,
-    collections::HashMap,
-    rc::{Rc, Weak},
-    str::FromStr,
-};
+use std::{collections::BTreeMap, fmt::Display, path::Path, str::FromStr};
 
 use color_eyre::Report;
 use itertools::Itertools;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use regex::Regex;
 
 use crate::*;
 
 use super::{Reportable, TaskResult};
 
-#[tracing::instrument]
-pub async fn part1() -> Result<Reportable> {
-    let mut input = read_file_lines("aoc2022/day7.txt").await?;
-    let mut fsspec: Vec<FSSpecLine> = Vec::new();
-    while let Some(line) = input.next().await {
-        let spec = line.parse()?;
-        trace!("Got spec line: {spec:?}");
-        fsspec.push(spec);
-    }
+pub mod validate;
 
-    let mut fs = FilesystemRef::empty_fs();
-
-    for spec in fsspec {
-        spec.apply_to(&mut fs)?;
-    }
+use validate::{validate_transcript, Anomaly};
 
-    fs.chtoroot();
+#[tracing::instrument]
+pub async fn part1() -> Result<Reportable> {
+    let fs = load_transcript("aoc2022/day7.txt").await?;
 
     let small_sums = fs
-        .dirs_at_most_sized(100000)
-        .iter()
-        .map(|x| x.total_size())
+        .walk(WalkOrder::PostOrder)
+        .filter(|(_, inode)| inode.is_dir() && inode.total_size() <= 100000)
+        .map(|(_, inode)| inode.total_size())
         .sum();
 
     Ok(Reportable {
@@ -47,36 +33,22 @@ pub async fn part1() -> Result<Reportable> {
 
 #[tracing::instrument]
 pub async fn part2() -> Result<Reportable> {
-    let mut input = read_file_lines("aoc2022/day7.txt").await?;
-    let mut fsspec: Vec<FSSpecLine> = Vec::new();
-    while let Some(line) = input.next().await {
-        let spec = line.parse()?;
-        trace!("Got spec line: {spec:?}");
-        fsspec.push(spec);
-    }
-
-    let mut fs = FilesystemRef::empty_fs();
-
-    for spec in fsspec {
-        spec.apply_to(&mut fs)?;
-    }
-
-    fs.chtoroot();
+    let fs = load_transcript("aoc2022/day7.txt").await?;
 
     let max_size = 70000000;
     let needed_free = 30000000;
-    let used_size = fs.current_dir.upgrade().unwrap().total_size();
+    let used_size = fs.stat("/")?.size;
     let unused_now = max_size - used_size;
     assert!(unused_now < needed_free);
     let to_be_freed = needed_free - unused_now;
 
     trace!("Need to free {to_be_freed}");
     let freed_by_delete = fs
-        .dirs_bigger_than_sized(to_be_freed)
-        .iter()
-        .min_by(|a, b| a.total_size().cmp(&b.total_size()))
-        .map(|x| x.total_size())
-        .unwrap();
+        .walk(WalkOrder::PostOrder)
+        .filter(|(_, inode)| inode.is_dir() && inode.total_size() >= to_be_freed)
+        .map(|(_, inode)| inode.total_size())
+        .min()
+        .ok_or_else(|| report!("no directory frees up {to_be_freed}"))?;
 
     Ok(Reportable {
         year: 2022,
@@ -86,6 +58,67 @@ pub async fn part2() -> Result<Reportable> {
     })
 }
 
+/// Reads and replays a transcript, warning about anything [validate_transcript] finds odd.
+/// Fails with all anomalies the replay can't get past if there are any.
+pub async fn load_transcript(filename: &str) -> Result<FilesystemRef> {
+    let input: Vec<String> = read_file_lines(filename).await?.collect().await;
+    let (fatal, odd): (Vec<_>, Vec<_>) = validate_transcript(&input)
+        .context(filename.to_string())?
+        .into_iter()
+        .partition(Anomaly::is_fatal);
+    if !fatal.is_empty() {
+        return Err(report!("{}", fatal.iter().join("\n"))).context(filename.to_string());
+    }
+    for anomaly in odd {
+        warn!("{filename}: {anomaly}");
+    }
+    FilesystemRef::from_transcript(input).context(filename.to_string())
+}
+
+/// Prints the filesystem as canonical transcript, `du` style or `tree` style
+pub fn print_fs(
+    fs: &FilesystemRef,
+    emit_transcript: bool,
+    du: bool,
+    max_depth: Option<usize>,
+) -> Result<()> {
+    if emit_transcript {
+        for line in fs.to_transcript() {
+            println!("{line}");
+        }
+    } else if du {
+        print!("{}", fs.render_du(max_depth));
+    } else {
+        print!("{}", fs.render_tree(max_depth));
+    }
+    Ok(())
+}
+
+/// Formats a size like `du -h`, rounding up to one decimal below 10 and to whole units above
+pub fn human_size(size: usize) -> String {
+    const UNITS: [&str; 5] = ["K", "M", "G", "T", "P"];
+    if size < 1024 {
+        return size.to_string();
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    loop {
+        let rounded = if value < 10.0 {
+            (value * 10.0).ceil() / 10.0
+        } else {
+            value.ceil()
+        };
+        if rounded >= 1024.0 && unit + 1 < UNITS.len() {
+            value /= 1024.0;
+            unit += 1;
+        } else if rounded < 10.0 {
+            return format!("{rounded:.1}{}", UNITS[unit]);
+        } else {
+            return format!("{rounded:.0}{}", UNITS[unit]);
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum FSSpecLine {
     Dir(String),
@@ -95,10 +128,21 @@ pub enum FSSpecLine {
 }
 
 impl FSSpecLine {
+    /// Entries that already exist with the same kind and size are left as they are,
+    /// so listing a directory again with the same contents changes nothing
     fn apply_to(&self, fs_ref: &mut FilesystemRef) -> Result<()> {
         match self {
-            FSSpecLine::Dir(dir) => fs_ref.create_dir(dir).context("creating directory"),
+            FSSpecLine::Dir(dir) => {
+                if fs_ref.resolve(dir).is_ok_and(|inode| inode.is_dir()) {
+                    return Ok(());
+                }
+                fs_ref.create_dir(dir).context("creating directory")
+            }
             FSSpecLine::File(file, size) => {
+                let existing = fs_ref.resolve(file).ok().map(|inode| &inode.data);
+                if matches!(existing, Some(INodeData::File(existing)) if existing == size) {
+                    return Ok(());
+                }
                 fs_ref.create_file(file, *size).context("creating file")
             }
             FSSpecLine::ChangeDir(dir) => fs_ref.change_dir(dir),
@@ -111,10 +155,10 @@ impl FromStr for FSSpecLine {
     type Err = Report;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let cd_regex = Regex::new(r#"\$ cd (?P<dir>.+)"#).unwrap();
-        let ls_regex = Regex::new(r#"\$ ls"#).unwrap();
-        let file_regex = Regex::new(r#"(?P<size>\d+) (?P<file>.+)"#).unwrap();
-        let dir_regex = Regex::new(r#"dir (?P<dir>.+)"#).unwrap();
+        let cd_regex = Regex::new(r#"^\$ cd (?P<dir>.+)$"#).unwrap();
+        let ls_regex = Regex::new(r#"^\$ ls$"#).unwrap();
+        let file_regex = Regex::new(r#"^(?P<size>\d+) (?P<file>.+)$"#).unwrap();
+        let dir_regex = Regex::new(r#"^dir (?P<dir>.+)$"#).unwrap();
 
         if let Some(captures) = cd_regex.captures(s) {
             Ok(Self::ChangeDir(
@@ -137,312 +181,708 @@ impl FromStr for FSSpecLine {
     }
 }
 
-#[derive(Debug)]
+impl Display for FSSpecLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FSSpecLine::Dir(dir) => write!(f, "dir {dir}"),
+            FSSpecLine::File(file, size) => write!(f, "{size} {file}"),
+            FSSpecLine::ChangeDir(dir) => write!(f, "$ cd {dir}"),
+            FSSpecLine::Listing => write!(f, "$ ls"),
+        }
+    }
+}
+
+/// Index of an inode in the arena of its [FilesystemRef]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+#[derive(Debug, Clone)]
 pub struct INode {
+    id: NodeId,
     name: String,
-    parent: Weak<INode>,
+    /// Containing directory, `None` only for the root
+    parent: Option<NodeId>,
+    /// Size of the file or of everything under the directory,
+    /// kept up to date by [FilesystemRef] whenever the tree changes
+    size: usize,
     data: INodeData,
 }
 
 impl INode {
-    pub fn name(self: &Rc<Self>) -> String {
-        self.name.clone()
+    pub fn id(&self) -> NodeId {
+        self.id
     }
-    // Constructs a root INode
-    pub fn root() -> Rc<Self> {
-        Rc::new_cyclic(|f| Self {
-            name: "".to_string(),
-            parent: f.clone(),
-            data: INodeData::empty_dir(),
-        })
+
+    pub fn name(&self) -> &str {
+        &self.name
     }
 
-    pub fn total_size(&self) -> usize {
-        match self.data {
-            INodeData::Directory(ref dirlist) => dirlist
-                .borrow()
-                .iter()
-                .map(|(_, value)| value.total_size())
-                .sum(),
-            INodeData::File(_) => self.data.size(),
+    pub fn is_root(&self) -> bool {
+        self.parent.is_none()
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.data.is_dir()
+    }
+
+    /// Returns the parent directory, `None` for the root
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    /// Looks up a direct child by name, always `None` for files
+    pub fn child(&self, name: &str) -> Option<NodeId> {
+        match &self.data {
+            INodeData::Directory(dirlist) => dirlist.get(name).copied(),
+            INodeData::File(_) => None,
         }
     }
 
-    pub fn new_file(name: String, parent: Weak<INode>, size: usize) -> Rc<INode> {
-        Rc::new(INode {
-            name,
-            parent,
-            data: INodeData::file(size),
-        })
+    /// Returns the direct children in name order, empty for files
+    pub fn child_ids(&self) -> impl DoubleEndedIterator<Item = NodeId> + '_ {
+        let dirlist = match &self.data {
+            INodeData::Directory(dirlist) => Some(dirlist.values().copied()),
+            INodeData::File(_) => None,
+        };
+        dirlist.into_iter().flatten()
     }
-    pub fn new_dir(name: String, parent: Weak<INode>) -> Rc<INode> {
-        Rc::new(INode {
-            name,
-            parent,
-            data: INodeData::empty_dir(),
-        })
+
+    /// Returns the cached size of the file or directory
+    pub fn total_size(&self) -> usize {
+        self.size
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum INodeData {
-    Directory(RefCell<HashMap<String, Rc<INode>>>),
+    Directory(BTreeMap<String, NodeId>),
     File(usize),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum INodeKind {
+    Directory,
+    File,
+}
+
+/// Metadata about a single inode, as returned by [FilesystemRef::stat]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stat {
+    pub path: String,
+    pub name: String,
+    pub kind: INodeKind,
+    /// Size of the file or the total size of everything under the directory
+    pub size: usize,
+    /// Number of direct children, 0 for files
+    pub entries: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalkOrder {
+    /// Directories are returned before their contents
+    PreOrder,
+    /// Directories are returned after their contents
+    PostOrder,
+}
+
+/// Recursive iterator over an inode and everything under it, see [FilesystemRef::walk]
+///
+/// Yields the depth relative to the starting inode together with the inode,
+/// children are visited in name order.
+pub struct Walk<'a> {
+    fs: &'a FilesystemRef,
+    order: WalkOrder,
+    stack: Vec<(usize, NodeId, bool)>,
+}
+
+impl<'a> Walk<'a> {
+    fn push_children(&mut self, depth: usize, id: NodeId) {
+        let inode = self.fs.node(id);
+        let first = self.stack.len();
+        self.stack
+            .extend(inode.child_ids().map(|child| (depth + 1, child, false)));
+        self.stack[first..].reverse();
+    }
+}
+
+impl<'a> Iterator for Walk<'a> {
+    type Item = (usize, &'a INode);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.order {
+            WalkOrder::PreOrder => {
+                let (depth, id, _) = self.stack.pop()?;
+                self.push_children(depth, id);
+                Some((depth, self.fs.node(id)))
+            }
+            WalkOrder::PostOrder => loop {
+                let (depth, id, expanded) = self.stack.last_mut()?;
+                if *expanded {
+                    let (depth, id, _) = self.stack.pop()?;
+                    return Some((depth, self.fs.node(id)));
+                }
+                *expanded = true;
+                let (depth, id) = (*depth, *id);
+                self.push_children(depth, id);
+            },
+        }
+    }
+}
+
+/// A filesystem tree stored in an arena of inodes together with a current directory.
+///
+/// Inodes refer to each other by [NodeId] only, so the whole tree is `Send + Sync`
+/// and can be shared with rayon. Slots of removed inodes are reused.
 #[derive(Debug, Clone)]
 pub struct FilesystemRef {
-    root: Rc<INode>,
-    current_dir: Weak<INode>,
+    nodes: Vec<Option<INode>>,
+    free: Vec<NodeId>,
+    current_dir: NodeId,
 }
 
 impl FilesystemRef {
+    const ROOT: NodeId = NodeId(0);
+
     pub fn empty_fs() -> Self {
-        let root = INode::root();
         Self {
-            current_dir: Rc::downgrade(&root),
-            root,
+            nodes: vec![Some(INode {
+                id: Self::ROOT,
+                name: String::new(),
+                parent: None,
+                size: 0,
+                data: INodeData::empty_dir(),
+            })],
+            free: Vec::new(),
+            current_dir: Self::ROOT,
         }
     }
 
-    pub fn chinode(&self, inode: &Rc<INode>) -> Self {
-        Self {
-            root: self.root.clone(),
-            current_dir: Rc::downgrade(inode),
+    /// Replays a `$ cd`/`$ ls` transcript into a fresh filesystem,
+    /// the returned reference points at the root.
+    pub fn from_transcript<S: AsRef<str>>(lines: impl IntoIterator<Item = S>) -> Result<Self> {
+        let mut fs = Self::empty_fs();
+        for (lineno, line) in lines.into_iter().enumerate() {
+            let spec: FSSpecLine = line.as_ref().parse()?;
+            trace!("Got spec line: {spec:?}");
+            spec.apply_to(&mut fs)
+                .with_context(|| format!("transcript line {}", lineno + 1))?;
+        }
+        fs.chtoroot();
+        Ok(fs)
+    }
+
+    /// Imports a directory tree from the host, file sizes are taken from the metadata.
+    /// Anything that is neither a file nor a directory, such as symlinks, is skipped.
+    pub async fn from_host_dir(path: impl AsRef<Path>) -> Result<Self> {
+        let mut fs = Self::empty_fs();
+        let mut pending = vec![(path.as_ref().to_path_buf(), String::new())];
+        while let Some((host_dir, fs_dir)) = pending.pop() {
+            let mut entries = tokio::fs::read_dir(&host_dir)
+                .await
+                .with_context(|| format!("reading {}", host_dir.display()))?;
+            while let Some(entry) = entries.next_entry().await? {
+                let name = entry.file_name().into_string().map_err(|name| {
+                    report!("{}: file name {name:?} is not UTF-8", host_dir.display())
+                })?;
+                let fs_path = format!("{fs_dir}/{name}");
+                let file_type = entry.file_type().await?;
+                if file_type.is_dir() {
+                    fs.create_dir(&fs_path)?;
+                    pending.push((entry.path(), fs_path));
+                } else if file_type.is_file() {
+                    let size = entry.metadata().await?.len() as usize;
+                    fs.create_file(&fs_path, size)?;
+                } else {
+                    trace!("Skipping {}", entry.path().display());
+                }
+            }
+        }
+        Ok(fs)
+    }
+
+    /// Generates a random tree with the given number of directories and files for stress testing
+    pub fn random(dirs: usize, files: usize, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut fs = Self::empty_fs();
+        let mut dir_paths = vec![String::new()];
+        for idx in 0..dirs {
+            let path = format!("{}/d{idx}", dir_paths[rng.gen_range(0..dir_paths.len())]);
+            fs.create_dir(&path).expect("generated names are unique");
+            dir_paths.push(path);
+        }
+        for idx in 0..files {
+            let path = format!("{}/f{idx}", dir_paths[rng.gen_range(0..dir_paths.len())]);
+            fs.create_file(&path, rng.gen_range(1..=1_000_000))
+                .expect("generated names are unique");
+        }
+        fs
+    }
+
+    /// Serialises the whole filesystem into a canonical transcript that starts at the root,
+    /// lists every directory once in name order and returns to the parent after each subdirectory.
+    pub fn to_transcript(&self) -> Vec<FSSpecLine> {
+        let mut lines = Vec::new();
+        let mut current_depth = 0;
+        let dirs = self
+            .walk_from(self.root(), WalkOrder::PreOrder)
+            .filter(|(_, inode)| inode.is_dir());
+        for (depth, dir) in dirs {
+            if depth == 0 {
+                lines.push(FSSpecLine::ChangeDir("/".to_string()));
+            } else {
+                // back up to the parent of this directory
+                lines.extend(
+                    (depth..=current_depth).map(|_| FSSpecLine::ChangeDir("..".to_string())),
+                );
+                lines.push(FSSpecLine::ChangeDir(dir.name.clone()));
+            }
+            current_depth = depth;
+            lines.push(FSSpecLine::Listing);
+            lines.extend(self.children(dir).iter().map(|child| match child.data {
+                INodeData::Directory(_) => FSSpecLine::Dir(child.name.clone()),
+                INodeData::File(size) => FSSpecLine::File(child.name.clone(), size),
+            }));
         }
+        lines.extend((0..current_depth).map(|_| FSSpecLine::ChangeDir("..".to_string())));
+        lines
+    }
+
+    /// Returns the inode for an id, `None` if it has been removed
+    pub fn get(&self, id: NodeId) -> Option<&INode> {
+        self.nodes.get(id.0).and_then(Option::as_ref)
+    }
+
+    fn node(&self, id: NodeId) -> &INode {
+        self.get(id)
+            .expect("inode ids are only kept for live inodes")
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut INode {
+        self.nodes[id.0]
+            .as_mut()
+            .expect("inode ids are only kept for live inodes")
+    }
+
+    /// Returns the direct children of a directory in name order, empty for files
+    pub fn children(&self, inode: &INode) -> Vec<&INode> {
+        inode.child_ids().map(|id| self.node(id)).collect_vec()
     }
 
     pub fn chtoroot(&mut self) {
-        self.current_dir = Rc::downgrade(&self.root);
+        self.current_dir = Self::ROOT;
     }
 
-    /// Returns only directories under the current directory
-    pub fn sub_dirs(&self) -> Vec<Rc<INode>> {
-        match self.current_dir.upgrade() {
-            None => panic!("Filesystem free'd while in use"),
-            Some(inode) => match &inode.data {
-                INodeData::Directory(dirlist) => dirlist
-                    .borrow()
-                    .iter()
-                    .filter(|(_, inode)| inode.data.is_dir())
-                    .map(|(_, inode)| inode.clone())
-                    .collect_vec(),
-                INodeData::File(_) => Vec::new(),
-            },
+    pub fn root(&self) -> &INode {
+        self.node(Self::ROOT)
+    }
+
+    pub fn cwd(&self) -> &INode {
+        self.node(self.current_dir)
+    }
+
+    /// Resolves an absolute or relative path to its inode.
+    ///
+    /// `.` and `..` are understood anywhere in the path, `..` at the root stays at the root.
+    pub fn resolve(&self, path: &str) -> Result<&INode> {
+        let mut inode = if path.starts_with('/') {
+            self.root()
+        } else {
+            self.cwd()
+        };
+        for component in path.split('/').filter(|c| !c.is_empty()) {
+            inode = match component {
+                "." => inode,
+                ".." => inode.parent().map_or(inode, |parent| self.node(parent)),
+                name => {
+                    if !inode.is_dir() {
+                        return Err(report!(
+                            "{path}: {} is not a directory",
+                            self.path_of(inode)
+                        ));
+                    }
+                    let child = inode
+                        .child(name)
+                        .ok_or_else(|| report!("{path}: no such file or directory"))?;
+                    self.node(child)
+                }
+            };
         }
+        Ok(inode)
     }
 
-    /// Returns only files under the current directory
-    pub fn sub_files(&self) -> Vec<Rc<INode>> {
-        match self.current_dir.upgrade() {
-            None => panic!("Filesystem free'd while in use"),
-            Some(inode) => match &inode.data {
-                INodeData::Directory(dirlist) => dirlist
-                    .borrow()
-                    .iter()
-                    .filter(|(_, inode)| !inode.data.is_dir())
-                    .map(|(_, inode)| inode.clone())
-                    .collect_vec(),
-                INodeData::File(_) => Vec::new(),
-            },
+    /// Resolves everything but the last component of a path, which is returned as name.
+    /// The parent must be a directory.
+    fn resolve_parent<'p>(&self, path: &'p str) -> Result<(NodeId, &'p str)> {
+        let path = path.trim_end_matches('/');
+        let (parent, name) = match path.rsplit_once('/') {
+            Some(("", name)) => (self.root(), name),
+            Some((parent, name)) => (self.resolve(parent)?, name),
+            None => (self.cwd(), path),
+        };
+        if matches!(name, "" | "." | "..") {
+            return Err(report!("{path}: invalid file name {name:?}"));
+        }
+        if !parent.is_dir() {
+            return Err(report!(
+                "{path}: {} is not a directory",
+                self.path_of(parent)
+            ));
+        }
+        Ok((parent.id, name))
+    }
+
+    /// Returns the absolute path of an inode
+    pub fn path_of(&self, inode: &INode) -> String {
+        let mut path = Vec::new();
+        let mut refp = inode;
+        while let Some(parent) = refp.parent {
+            path.push(refp.name());
+            refp = self.node(parent);
+        }
+        if path.is_empty() {
+            "/".to_string()
+        } else {
+            // push root
+            path.push("");
+            path.reverse();
+            path.join("/")
         }
     }
 
-    pub fn sub_inodes(&self) -> Vec<Rc<INode>> {
-        match self.current_dir.upgrade() {
-            None => panic!("Filesystem free'd while in use"),
-            Some(inode) => match &inode.data {
-                INodeData::Directory(dirlist) => dirlist
-                    .borrow()
-                    .iter()
-                    .map(|(_, inode)| inode.clone())
-                    .collect_vec(),
-                INodeData::File(_) => Vec::new(),
+    pub fn stat(&self, path: &str) -> Result<Stat> {
+        Ok(self.stat_of(self.resolve(path)?))
+    }
+
+    fn stat_of(&self, inode: &INode) -> Stat {
+        Stat {
+            path: self.path_of(inode),
+            name: inode.name.clone(),
+            kind: if inode.is_dir() {
+                INodeKind::Directory
+            } else {
+                INodeKind::File
             },
+            size: inode.total_size(),
+            entries: inode.child_ids().count(),
+        }
+    }
+
+    /// Walks the current directory and everything under it
+    pub fn walk(&self, order: WalkOrder) -> Walk<'_> {
+        self.walk_from(self.cwd(), order)
+    }
+
+    /// Walks the given inode and everything under it
+    pub fn walk_from(&self, start: &INode, order: WalkOrder) -> Walk<'_> {
+        Walk {
+            fs: self,
+            order,
+            stack: vec![(0, start.id, false)],
         }
     }
 
-    /// Returns a list of all directories under this one that are smaller than the given size.
-    /// The directory itself is not returned when it is smaller
+    /// Sums up every directory under the current one from the file sizes alone, without
+    /// the cached sizes. The direct children of the current directory are sized in parallel
+    /// with rayon, each of them from a post-order walk.
     ///
-    /// This will recurse into all subdirectories
+    /// Directories are returned in post-order together with their size,
+    /// the current directory comes last.
+    pub fn par_dir_sizes(&self) -> Vec<(NodeId, usize)> {
+        let cwd = self.cwd();
+        let subtrees = cwd.child_ids().collect_vec();
+        let (size, mut dirs) = subtrees
+            .par_iter()
+            .map(|id| self.sizes_under(self.node(*id)))
+            .reduce(
+                || (0, Vec::new()),
+                |(size_a, mut dirs_a), (size_b, dirs_b)| {
+                    dirs_a.extend(dirs_b);
+                    (size_a + size_b, dirs_a)
+                },
+            );
+        dirs.push((cwd.id, size));
+        dirs
+    }
+
+    /// Sums up the given inode and every directory under it, see [Self::par_dir_sizes]
+    fn sizes_under(&self, start: &INode) -> (usize, Vec<(NodeId, usize)>) {
+        // pending[d] sums up the children seen so far of the open directory at depth d - 1
+        let mut pending = Vec::new();
+        let mut dirs = Vec::new();
+        for (depth, inode) in self.walk_from(start, WalkOrder::PostOrder) {
+            let size = match &inode.data {
+                INodeData::File(size) => *size,
+                INodeData::Directory(_) => {
+                    let size = pending.get_mut(depth + 1).map_or(0, std::mem::take);
+                    dirs.push((inode.id, size));
+                    size
+                }
+            };
+            if pending.len() <= depth {
+                pending.resize(depth + 1, 0);
+            }
+            pending[depth] += size;
+        }
+        (pending[0], dirs)
+    }
+
+    /// Renders the current directory like `tree -s`, directories are marked with a trailing `/`.
+    /// Entries deeper than `max_depth` below the current directory are left out.
+    pub fn render_tree(&self, max_depth: Option<usize>) -> String {
+        let cwd = self.cwd();
+        let width = cwd.total_size().to_string().len();
+        let mut out = format!("[{:>width$}]  {}\n", cwd.total_size(), self.path_of(cwd));
+        // indents[d] continues the branch of the ancestor at depth d + 1 down to its children
+        let mut indents = Vec::new();
+        for (depth, inode) in self.walk(WalkOrder::PreOrder).skip(1) {
+            if max_depth.is_some_and(|max_depth| depth > max_depth) {
+                continue;
+            }
+            let parent = self.node(inode.parent.expect("only the root has no parent"));
+            let last = parent.child_ids().next_back() == Some(inode.id);
+            let (branch, indent) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            indents.truncate(depth - 1);
+            let marker = if inode.is_dir() { "/" } else { "" };
+            out.push_str(&format!(
+                "{}{branch}[{:>width$}]  {}{marker}\n",
+                indents.concat(),
+                inode.total_size(),
+                inode.name()
+            ));
+            indents.push(indent);
+        }
+        out
+    }
+
+    /// Renders the directory totals under the current directory like `du -h --max-depth N`,
+    /// sorted largest first. Without a depth limit every directory is listed.
+    pub fn render_du(&self, max_depth: Option<usize>) -> String {
+        self.walk(WalkOrder::PostOrder)
+            .filter(|(depth, inode)| {
+                inode.is_dir() && max_depth.is_none_or(|max_depth| *depth <= max_depth)
+            })
+            .map(|(_, inode)| (inode.total_size(), self.path_of(inode)))
+            .sorted_by(|(size_a, path_a), (size_b, path_b)| {
+                size_b.cmp(size_a).then_with(|| path_a.cmp(path_b))
+            })
+            .map(|(size, path)| format!("{}\t{path}\n", human_size(size)))
+            .collect()
+    }
+
+    /// Returns only directories under the current directory
+    pub fn sub_dirs(&self) -> Vec<&INode> {
+        self.children(self.cwd())
+            .into_iter()
+            .filter(|inode| inode.is_dir())
+            .collect_vec()
+    }
+
+    /// Returns only files under the current directory
+    pub fn sub_files(&self) -> Vec<&INode> {
+        self.children(self.cwd())
+            .into_iter()
+            .filter(|inode| !inode.is_dir())
+            .collect_vec()
+    }
+
+    pub fn sub_inodes(&self) -> Vec<&INode> {
+        self.children(self.cwd())
+    }
+
+    /// Returns all directories under the current one matching the given size predicate.
+    /// The current directory itself is never returned.
     ///
-    /// Files from subdirectories are returned first
-    pub fn dirs_at_most_sized(&self, size_max: usize) -> Vec<Rc<INode>> {
+    /// Directories are returned in post-order, so subdirectories come before their parents.
+    pub fn dirs_sized(&self, pred: impl Fn(usize) -> bool) -> Vec<&INode> {
+        self.walk(WalkOrder::PostOrder)
+            .filter(|(depth, inode)| *depth > 0 && inode.is_dir())
+            .filter(|(_, inode)| {
+                trace!(
+                    "Checking folder {} with size {}",
+                    self.path_of(inode),
+                    inode.total_size()
+                );
+                pred(inode.total_size())
+            })
+            .map(|(_, inode)| inode)
+            .collect_vec()
+    }
+
+    /// Returns a list of all directories under this one that are at most the given size
+    pub fn dirs_at_most_sized(&self, size_max: usize) -> Vec<&INode> {
         trace!(
             "Looking for small folders under {size_max} at {}",
             self.current_path()
         );
-        let smaller = self.sub_dirs().into_iter().filter(|x| {
-            trace!(
-                "Checking folder {} at {} if smaller than {size_max}: {}",
-                x.name(),
-                self.current_path(),
-                x.total_size()
-            );
-            x.total_size() <= size_max
-        });
-        self.sub_dirs()
-            .iter()
-            .flat_map(|x| {
-                let fs_ref = self.chinode(x);
-                fs_ref.dirs_at_most_sized(size_max)
-            })
-            .chain(smaller)
-            .collect_vec()
+        self.dirs_sized(|size| size <= size_max)
     }
 
     /// Returns directories of atleast this size
-    pub fn dirs_bigger_than_sized(&self, size_min: usize) -> Vec<Rc<INode>> {
+    pub fn dirs_bigger_than_sized(&self, size_min: usize) -> Vec<&INode> {
         trace!(
-            "Looking for small folders under {size_min} at {}",
+            "Looking for big folders over {size_min} at {}",
             self.current_path()
         );
-        let smaller = self.sub_dirs().into_iter().filter(|x| {
-            trace!(
-                "Checking folder {} at {} if smaller than {size_min}: {}",
-                x.name(),
-                self.current_path(),
-                x.total_size()
-            );
-            x.total_size() >= size_min
-        });
-        self.sub_dirs()
-            .iter()
-            .flat_map(|x| {
-                let fs_ref = self.chinode(x);
-                fs_ref.dirs_bigger_than_sized(size_min)
-            })
-            .chain(smaller)
-            .collect_vec()
+        self.dirs_sized(|size| size >= size_min)
     }
 
     pub fn current_path(&self) -> String {
-        let mut path = Vec::new();
-        let mut refp = self.current_dir.clone();
-        while refp
-            .upgrade()
-            .map(|refpc| {
-                // the root is it's own parent
-                !std::ptr::eq(refpc.parent.as_ptr(), Rc::as_ptr(&refpc))
-            })
-            .unwrap()
-        {
-            let refpr = refp.upgrade().unwrap();
-            path.push(refpr.name());
-            refp = refpr.parent.clone();
+        self.path_of(self.cwd())
+    }
+
+    fn insert(&mut self, path: &str, data: INodeData, size: usize) -> Result<()> {
+        let (parent, name) = self.resolve_parent(path)?;
+        if self.node(parent).child(name).is_some() {
+            return Err(report!("{path}: file or directory exists"));
         }
-        if path.is_empty() {
-            "/".to_string()
-        } else {
-            // push root
-            path.push(String::new());
-            path.reverse();
-            path.join("/")
+        let inode = INode {
+            id: NodeId(self.nodes.len()),
+            name: name.to_owned(),
+            parent: Some(parent),
+            size,
+            data,
+        };
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.nodes[id.0] = Some(INode { id, ..inode });
+                id
+            }
+            None => {
+                let id = inode.id;
+                self.nodes.push(Some(inode));
+                id
+            }
+        };
+        self.link(parent, name, id);
+        Ok(())
+    }
+
+    /// Adds an inode to the listing of a directory and its size to all the parents
+    fn link(&mut self, parent: NodeId, name: &str, id: NodeId) {
+        let size = self.node(id).size;
+        let INodeData::Directory(dirlist) = &mut self.node_mut(parent).data else {
+            unreachable!("resolve_parent only returns directories")
+        };
+        dirlist.insert(name.to_owned(), id);
+        let mut refp = Some(parent);
+        while let Some(id) = refp {
+            let inode = self.node_mut(id);
+            inode.size += size;
+            refp = inode.parent;
         }
     }
 
-    pub fn create_dir(&mut self, dir: &str) -> Result<()> {
-        assert!(
-            match self.current_dir.upgrade() {
-                None => panic!("Filesystem free'd while in use"),
-                Some(v) => v.data.is_dir(),
-            },
-            "Must create file in directory"
-        );
+    /// Removes an inode from the listing of its directory and its size from all the parents
+    fn unlink(&mut self, id: NodeId) {
+        let (parent, name, size) = {
+            let inode = self.node(id);
+            let parent = inode.parent.expect("the root is never unlinked");
+            (parent, inode.name.clone(), inode.size)
+        };
+        let INodeData::Directory(dirlist) = &mut self.node_mut(parent).data else {
+            unreachable!("parents are always directories")
+        };
+        dirlist.remove(&name);
+        let mut refp = Some(parent);
+        while let Some(id) = refp {
+            let inode = self.node_mut(id);
+            inode.size -= size;
+            refp = inode.parent;
+        }
+    }
 
+    pub fn create_dir(&mut self, dir: &str) -> Result<()> {
         trace!("Creating directory {dir} in {:?}", self.current_path());
-
-        match self.current_dir.upgrade() {
-            None => panic!("Filesystem free'd while in use"),
-            Some(v) => match &v.data {
-                INodeData::File(_) => unreachable!(),
-                INodeData::Directory(q) => {
-                    let mut cdir = q.borrow_mut();
-                    if cdir.contains_key(dir) {
-                        return Err(Report::msg("file or directory exists"));
-                    }
-                    match cdir.insert(
-                        dir.to_owned(),
-                        INode::new_dir(dir.to_owned(), Rc::downgrade(&v)),
-                    ) {
-                        Some(_) => unreachable!(),
-                        None => Ok(()),
-                    }
-                }
-            },
-        }
+        self.insert(dir, INodeData::empty_dir(), 0)
     }
-    pub fn create_file(&mut self, file: &str, size: usize) -> Result<()> {
-        assert!(
-            match self.current_dir.upgrade() {
-                None => panic!("Filesystem free'd while in use"),
-                Some(v) => v.data.is_dir(),
-            },
-            "Must create file in directory"
-        );
 
+    pub fn create_file(&mut self, file: &str, size: usize) -> Result<()> {
         trace!(
             "Creating file {file} with size {size} in {:?}",
             self.current_path()
         );
-
-        match self.current_dir.upgrade() {
-            None => panic!("Filesystem free'd while in use"),
-            Some(v) => match &v.data {
-                INodeData::File(_) => unreachable!(),
-                INodeData::Directory(q) => {
-                    let mut dir = q.borrow_mut();
-                    if dir.contains_key(file) {
-                        return Err(Report::msg("file or directory exists"));
-                    }
-                    match dir.insert(
-                        file.to_owned(),
-                        INode::new_file(file.to_owned(), Rc::downgrade(&v), size),
-                    ) {
-                        Some(_) => unreachable!(),
-                        None => Ok(()),
-                    }
-                }
-            },
-        }
+        self.insert(file, INodeData::file(size), size)
     }
+
+    /// Changes into the given absolute or relative path, which must be a directory
     pub fn change_dir(&mut self, dir: &str) -> Result<()> {
         trace!("Changing directory {} via {dir}", self.current_path());
 
-        let res = match dir {
-            "." => Ok(()),
-            ".." => {
-                self.current_dir = match self.current_dir.upgrade() {
-                    None => panic!("Filesystem free'd while in use"),
-                    Some(cd) => cd.parent.clone(),
-                };
-                Ok(())
-            }
-            "/" => {
-                self.current_dir = Rc::downgrade(&self.root);
-                Ok(())
-            }
-            dir => {
-                let cd = match self.current_dir.upgrade() {
-                    None => panic!("Filesystem free'd while in use"),
-                    Some(cd) => cd,
-                };
-                assert!(cd.data.is_dir(), "Current directory is file");
-                match cd.data {
-                    INodeData::Directory(ref v) => match v.borrow().get(dir) {
-                        None => Err(Report::msg(format!("directory {dir} does not exist"))),
-                        Some(v) => {
-                            self.current_dir = Rc::downgrade(v);
-                            Ok(())
-                        }
-                    },
-                    INodeData::File(_) => unreachable!(),
-                }
-            }
-        };
+        let target = self.resolve(dir)?;
+        if !target.is_dir() {
+            return Err(report!("{dir}: not a directory"));
+        }
+        self.current_dir = target.id;
+
         trace!("New current directory: {}", self.current_path());
-        res
+        Ok(())
+    }
+
+    /// Removes a file or a directory with everything under it and returns what it was.
+    ///
+    /// The root as well as the current directory and its parents cannot be removed.
+    pub fn remove(&mut self, path: &str) -> Result<Stat> {
+        let (parent, name) = self.resolve_parent(path)?;
+        let id = self
+            .node(parent)
+            .child(name)
+            .ok_or_else(|| report!("{path}: no such file or directory"))?;
+        if self.is_ancestor_of(id, self.current_dir) {
+            return Err(report!(
+                "{path}: cannot remove the current directory or its parents"
+            ));
+        }
+        let stat = self.stat_of(self.node(id));
+        let removed = self
+            .walk_from(self.node(id), WalkOrder::PostOrder)
+            .map(|(_, inode)| inode.id)
+            .collect_vec();
+        self.unlink(id);
+        for id in removed {
+            self.nodes[id.0] = None;
+            self.free.push(id);
+        }
+        trace!("Removed {path}");
+        Ok(stat)
+    }
+
+    /// Moves a file or directory to a new path, the target must not exist yet.
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<()> {
+        let (from_parent, from_name) = self.resolve_parent(from)?;
+        let id = self
+            .node(from_parent)
+            .child(from_name)
+            .ok_or_else(|| report!("{from}: no such file or directory"))?;
+        let (to_parent, to_name) = self.resolve_parent(to)?;
+        if self.is_ancestor_of(id, to_parent) {
+            return Err(report!("{to}: cannot move {from} into itself"));
+        }
+        if self.node(to_parent).child(to_name).is_some() {
+            return Err(report!("{to}: file or directory exists"));
+        }
+        self.unlink(id);
+        let inode = self.node_mut(id);
+        inode.name = to_name.to_owned();
+        inode.parent = Some(to_parent);
+        self.link(to_parent, to_name, id);
+        trace!("Renamed {from} to {to}");
+        Ok(())
+    }
+
+    /// Returns true if `ancestor` is `other` or one of its parents
+    fn is_ancestor_of(&self, ancestor: NodeId, other: NodeId) -> bool {
+        let mut refp = Some(other);
+        while let Some(id) = refp {
+            if id == ancestor {
+                return true;
+            }
+            refp = self.node(id).parent;
+        }
+        false
     }
 }
 
@@ -460,9 +900,424 @@ impl INodeData {
         }
     }
     pub fn empty_dir() -> Self {
-        INodeData::Directory(RefCell::new(HashMap::new()))
+        INodeData::Directory(BTreeMap::new())
     }
     pub fn file(size: usize) -> Self {
         INodeData::File(size)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    pub(super) const EXAMPLE: &str = "$ cd /
+$ ls
+dir a
+14848514 b.txt
+8504156 c.dat
+dir d
+$ cd a
+$ ls
+dir e
+29116 f
+2557 g
+62596 h.lst
+$ cd e
+$ ls
+584 i
+$ cd ..
+$ cd ..
+$ cd d
+$ ls
+4060174 j
+8033020 d.log
+5626152 d.ext
+7214296 k";
+
+    fn example() -> FilesystemRef {
+        FilesystemRef::from_transcript(EXAMPLE.lines()).unwrap()
+    }
+
+    #[test]
+    fn test_example_sizes() {
+        let fs = example();
+        let small: usize = fs
+            .dirs_at_most_sized(100000)
+            .iter()
+            .map(|x| x.total_size())
+            .sum();
+        assert_eq!(small, 95437);
+        assert_eq!(fs.stat("/").unwrap().size, 48381165);
+    }
+
+    #[test]
+    fn test_render_tree() {
+        let fs = example();
+        assert_eq!(
+            fs.render_tree(None),
+            "[48381165]  /
+├── [   94853]  a/
+│   ├── [     584]  e/
+│   │   └── [     584]  i
+│   ├── [   29116]  f
+│   ├── [    2557]  g
+│   └── [   62596]  h.lst
+├── [14848514]  b.txt
+├── [ 8504156]  c.dat
+└── [24933642]  d/
+    ├── [ 5626152]  d.ext
+    ├── [ 8033020]  d.log
+    ├── [ 4060174]  j
+    └── [ 7214296]  k
+"
+        );
+        let mut fs = fs.clone();
+        fs.change_dir("/a").unwrap();
+        assert_eq!(
+            fs.render_tree(Some(1)),
+            "[94853]  /a
+├── [  584]  e/
+├── [29116]  f
+├── [ 2557]  g
+└── [62596]  h.lst
+"
+        );
+    }
+
+    #[test]
+    fn test_render_deep_tree() {
+        let fs = deep_chain(5000);
+        let tree = fs.render_tree(None);
+        assert_eq!(tree.lines().count(), 5002);
+        assert_eq!(
+            tree.lines().last(),
+            Some(format!("{}└── [1]  f", "    ".repeat(5000)).as_str())
+        );
+        assert_eq!(
+            fs.render_tree(Some(2)),
+            "[1]  /\n└── [1]  d/\n    └── [1]  d/\n"
+        );
+    }
+
+    #[test]
+    fn test_render_du() {
+        let fs = example();
+        assert_eq!(fs.render_du(None), "47M\t/\n24M\t/d\n93K\t/a\n584\t/a/e\n");
+        assert_eq!(fs.render_du(Some(0)), "47M\t/\n");
+        assert_eq!(human_size(1024), "1.0K");
+        assert_eq!(human_size(1025), "1.1K");
+        assert_eq!(human_size(10 * 1024), "10K");
+        assert_eq!(human_size(1024 * 1024 - 1), "1.0M");
+    }
+
+    #[test]
+    fn test_transcript_round_trip() {
+        let fs = example();
+        let transcript = fs
+            .to_transcript()
+            .iter()
+            .map(|x| x.to_string())
+            .collect_vec();
+        assert_eq!(
+            transcript[..8].join("\n"),
+            "$ cd /\n$ ls\ndir a\n14848514 b.txt\n8504156 c.dat\ndir d\n$ cd a\n$ ls"
+        );
+        let replayed = FilesystemRef::from_transcript(&transcript).unwrap();
+        assert_eq!(replayed.render_tree(None), fs.render_tree(None));
+
+        let fs = FilesystemRef::random(200, 1000, 7);
+        let transcript = fs
+            .to_transcript()
+            .iter()
+            .map(|x| x.to_string())
+            .collect_vec();
+        let replayed = FilesystemRef::from_transcript(&transcript).unwrap();
+        assert_eq!(replayed.render_du(None), fs.render_du(None));
+        assert_eq!(replayed.stat("/").unwrap(), fs.stat("/").unwrap());
+        assert_eq!(replayed.walk(WalkOrder::PreOrder).count(), 1 + 200 + 1000);
+        assert_eq!(
+            replayed
+                .to_transcript()
+                .iter()
+                .map(|x| x.to_string())
+                .collect_vec(),
+            transcript
+        );
+    }
+
+    #[test]
+    fn test_deep_transcript_round_trip() {
+        let fs = deep_chain(10000);
+        let transcript = fs.to_transcript();
+        // a cd, a listing and one entry for every directory, then back up to the root
+        assert_eq!(transcript.len(), 10001 * 3 + 10000);
+        let mut replayed = FilesystemRef::empty_fs();
+        for line in &transcript {
+            line.apply_to(&mut replayed).unwrap();
+        }
+        assert_eq!(replayed.current_path(), "/");
+        assert_eq!(
+            replayed
+                .walk(WalkOrder::PostOrder)
+                .map(|(depth, _)| depth)
+                .max(),
+            Some(10001)
+        );
+        assert_eq!(replayed.stat("/").unwrap(), fs.stat("/").unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_repeated_listing() {
+        let relisted =
+            format!("{EXAMPLE}\n$ cd /\n$ ls\ndir a\n14848514 b.txt\n8504156 c.dat\ndir d");
+        let fs = FilesystemRef::from_transcript(relisted.lines()).unwrap();
+        assert_eq!(fs.render_tree(None), example().render_tree(None));
+
+        let path = std::env::temp_dir().join(format!("aoc-{}-day7-relisted", std::process::id()));
+        tokio::fs::write(&path, &relisted).await.unwrap();
+        let relisted = load_transcript(path.to_str().unwrap()).await;
+        tokio::fs::write(&path, "$ cd /\n$ ls\n10 b\n$ ls\n12 b\n$ cd c")
+            .await
+            .unwrap();
+        let conflicting = load_transcript(path.to_str().unwrap()).await;
+        tokio::fs::remove_file(&path).await.unwrap();
+
+        assert_eq!(relisted.unwrap().stat("/").unwrap().size, 48381165);
+        assert_eq!(
+            format!("{:#}", conflicting.unwrap_err()),
+            format!(
+                "{}: line 5: /b has size 12, but had size 10 on line 3\n\
+                 line 6: cd into /c, which no listing declared",
+                path.display()
+            )
+        );
+    }
+
+    #[test]
+    fn test_spec_line() {
+        for line in ["$ cd ..", "$ ls", "dir 123 x", "42 dir x", "7 a b"] {
+            assert_eq!(line.parse::<FSSpecLine>().unwrap().to_string(), line);
+        }
+        assert!(matches!(
+            "dir 123 x".parse::<FSSpecLine>().unwrap(),
+            FSSpecLine::Dir(_)
+        ));
+        assert!("$ rm -rf".parse::<FSSpecLine>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_host_dir() {
+        let path = std::env::temp_dir().join(format!("aoc-{}-day7", std::process::id()));
+        tokio::fs::create_dir_all(path.join("a/e")).await.unwrap();
+        tokio::fs::create_dir_all(path.join("d")).await.unwrap();
+        tokio::fs::write(path.join("b.txt"), vec![0; 1500])
+            .await
+            .unwrap();
+        tokio::fs::write(path.join("a/f"), vec![0; 29])
+            .await
+            .unwrap();
+        tokio::fs::write(path.join("a/e/i"), vec![0; 584])
+            .await
+            .unwrap();
+        let fs = FilesystemRef::from_host_dir(&path).await;
+        tokio::fs::remove_dir_all(&path).await.unwrap();
+
+        let fs = fs.unwrap();
+        assert_eq!(
+            fs.render_tree(None),
+            "[2113]  /
+├── [ 613]  a/
+│   ├── [ 584]  e/
+│   │   └── [ 584]  i
+│   └── [  29]  f
+├── [1500]  b.txt
+└── [   0]  d/
+"
+        );
+        assert_eq!(fs.render_du(None), "2.1K\t/\n613\t/a\n584\t/a/e\n0\t/d\n");
+    }
+
+    #[test]
+    fn test_resolve() {
+        let mut fs = example();
+        assert_eq!(fs.resolve("/a/e/i").unwrap().total_size(), 584);
+        fs.change_dir("/a/e").unwrap();
+        assert_eq!(fs.current_path(), "/a/e");
+        assert_eq!(fs.resolve("i").unwrap().name(), "i");
+        assert_eq!(fs.resolve("./../f").unwrap().total_size(), 29116);
+        assert_eq!(fs.path_of(fs.resolve("../../../d/j").unwrap()), "/d/j");
+        fs.change_dir("..").unwrap();
+        assert_eq!(fs.current_path(), "/a");
+
+        assert!(fs.resolve("/nope").is_err());
+        assert!(fs.resolve("/b.txt/x").is_err());
+        assert!(fs.change_dir("/b.txt").is_err());
+        assert_eq!(fs.current_path(), "/a");
+        assert!(fs.create_file("/b.txt/x", 1).is_err());
+        assert!(fs.create_dir("f").is_err());
+    }
+
+    #[test]
+    fn test_stat() {
+        let fs = example();
+        let stat = fs.stat("/a").unwrap();
+        assert_eq!(
+            stat,
+            Stat {
+                path: "/a".to_string(),
+                name: "a".to_string(),
+                kind: INodeKind::Directory,
+                size: 94853,
+                entries: 4,
+            }
+        );
+        let stat = fs.stat("/d/k").unwrap();
+        assert_eq!(stat.kind, INodeKind::File);
+        assert_eq!(stat.size, 7214296);
+        assert_eq!(stat.entries, 0);
+    }
+
+    #[test]
+    fn test_walk() {
+        let fs = example();
+        let pre = fs
+            .walk(WalkOrder::PreOrder)
+            .map(|(depth, inode)| format!("{depth}{}", inode.name()))
+            .join(" ");
+        assert_eq!(
+            pre,
+            "0 1a 2e 3i 2f 2g 2h.lst 1b.txt 1c.dat 1d 2d.ext 2d.log 2j 2k"
+        );
+        let post = fs
+            .walk_from(fs.resolve("/a").unwrap(), WalkOrder::PostOrder)
+            .map(|(depth, inode)| format!("{depth}{}", inode.name()))
+            .join(" ");
+        assert_eq!(post, "2i 1e 1f 1g 1h.lst 0a");
+    }
+
+    #[test]
+    fn test_remove_rename() {
+        let mut fs = example();
+        fs.change_dir("/a/e").unwrap();
+        assert!(fs.remove("/").is_err());
+        assert!(fs.remove("/a").is_err());
+        assert!(fs.remove(".").is_err());
+        assert!(fs.remove("/missing").is_err());
+
+        let removed = fs.remove("/d").unwrap();
+        assert_eq!(removed.path, "/d");
+        assert_eq!(removed.size, 24933642);
+        assert!(fs.resolve("/d/j").is_err());
+        assert_eq!(fs.stat("/").unwrap().size, 48381165 - 24933642);
+
+        fs.rename("i", "/a/moved").unwrap();
+        assert!(fs.resolve("/a/e/i").is_err());
+        assert_eq!(fs.path_of(fs.resolve("/a/moved").unwrap()), "/a/moved");
+        assert!(fs.rename("/a", "/a/e/a").is_err());
+        assert!(fs.rename("/a/f", "/a/g").is_err());
+
+        fs.rename("/a", "/z").unwrap();
+        assert_eq!(fs.current_path(), "/z/e");
+        assert_eq!(fs.stat("/z").unwrap().entries, 5);
+    }
+
+    /// A single file at the end of a chain of `depth` nested directories
+    fn deep_chain(depth: usize) -> FilesystemRef {
+        let mut fs = FilesystemRef::empty_fs();
+        for _ in 0..depth {
+            fs.create_dir("d").unwrap();
+            fs.change_dir("d").unwrap();
+        }
+        fs.create_file("f", 1).unwrap();
+        fs.chtoroot();
+        fs
+    }
+
+    fn summed_size(fs: &FilesystemRef, inode: &INode) -> usize {
+        match &inode.data {
+            INodeData::Directory(_) => fs
+                .children(inode)
+                .into_iter()
+                .map(|c| summed_size(fs, c))
+                .sum(),
+            INodeData::File(size) => *size,
+        }
+    }
+
+    #[test]
+    fn test_cached_size() {
+        let mut fs = example();
+        fs.create_dir("/a/new").unwrap();
+        fs.create_file("/a/new/x", 1000).unwrap();
+        fs.rename("/a/e", "/d/e").unwrap();
+        fs.rename("/a/new", "/a/e").unwrap();
+        fs.remove("/d/d.log").unwrap();
+        fs.rename("/b.txt", "/d/e/b.txt").unwrap();
+
+        for (_, inode) in fs.walk(WalkOrder::PreOrder) {
+            assert_eq!(
+                inode.total_size(),
+                summed_size(&fs, inode),
+                "{}",
+                fs.path_of(inode)
+            );
+        }
+        assert_eq!(
+            fs.stat("/d").unwrap().size,
+            24933642 - 8033020 + 584 + 14848514
+        );
+        assert_eq!(fs.stat("/a").unwrap().size, 94853 - 584 + 1000);
+
+        let small = fs.dirs_at_most_sized(100000);
+        assert_eq!(small.iter().map(|x| fs.path_of(x)).join(" "), "/a/e /a");
+
+        // removed slots are reused by new inodes
+        let slots = fs.nodes.len();
+        fs.remove("/d").unwrap();
+        fs.create_dir("/n").unwrap();
+        fs.create_file("/n/y", 5).unwrap();
+        assert_eq!(fs.nodes.len(), slots);
+        assert_eq!(fs.stat("/n").unwrap().size, 5);
+    }
+
+    #[test]
+    fn test_deep_par_dir_sizes() {
+        let fs = deep_chain(10000);
+        let sizes = fs.par_dir_sizes();
+        assert_eq!(sizes.len(), 10001);
+        assert!(sizes.iter().all(|(_, size)| *size == 1));
+        assert_eq!(sizes.last(), Some(&(fs.root().id(), 1)));
+    }
+
+    #[test]
+    fn test_par_dir_sizes() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<FilesystemRef>();
+
+        let fs = FilesystemRef::random(2000, 20000, 41);
+        let sizes = fs.par_dir_sizes();
+        assert_eq!(sizes.len(), 2001);
+        assert_eq!(
+            sizes.last(),
+            Some(&(fs.root().id(), fs.root().total_size()))
+        );
+        let cached = fs
+            .walk(WalkOrder::PostOrder)
+            .filter(|(_, inode)| inode.is_dir())
+            .map(|(_, inode)| (inode.id(), inode.total_size()))
+            .collect_vec();
+        assert_eq!(sizes, cached);
+
+        let totals = std::thread::scope(|scope| {
+            fs.sub_dirs()
+                .into_iter()
+                .map(|dir| scope.spawn(|| fs.walk_from(dir, WalkOrder::PreOrder).count()))
+                .collect_vec()
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .sum::<usize>()
+        });
+        assert_eq!(totals + fs.sub_files().len() + 1, 1 + 2000 + 20000);
+    }
+}
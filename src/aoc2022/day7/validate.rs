@@ -0,0 +1,316 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use itertools::Itertools;
+
+use crate::*;
+
+use super::FSSpecLine;
+
+/// Something suspicious in a shell transcript, all line numbers start at 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Anomaly {
+    /// A directory was listed a second time
+    DuplicateListing {
+        line: usize,
+        path: String,
+        first_line: usize,
+    },
+    /// A directory or file entry without a `$ ls` in the current directory before it
+    OutputWithoutListing { line: usize, path: String },
+    /// The same file was listed with different sizes
+    ConflictingSize {
+        line: usize,
+        path: String,
+        size: usize,
+        first_line: usize,
+        first_size: usize,
+    },
+    /// The same name was used for both a file and a directory
+    ConflictingKind {
+        line: usize,
+        path: String,
+        first_line: usize,
+    },
+    /// `$ cd` into a directory that no listing declared
+    UndeclaredDirectory { line: usize, path: String },
+    /// A declared directory was never listed, so everything below it is missing
+    UnvisitedDirectory { line: usize, path: String },
+}
+
+impl Anomaly {
+    pub fn line(&self) -> usize {
+        match self {
+            Anomaly::DuplicateListing { line, .. }
+            | Anomaly::OutputWithoutListing { line, .. }
+            | Anomaly::ConflictingSize { line, .. }
+            | Anomaly::ConflictingKind { line, .. }
+            | Anomaly::UndeclaredDirectory { line, .. }
+            | Anomaly::UnvisitedDirectory { line, .. } => *line,
+        }
+    }
+
+    /// Whether replaying the transcript stops at this anomaly
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            Anomaly::ConflictingSize { .. }
+                | Anomaly::ConflictingKind { .. }
+                | Anomaly::UndeclaredDirectory { .. }
+        )
+    }
+}
+
+impl Display for Anomaly {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: ", self.line())?;
+        match self {
+            Anomaly::DuplicateListing {
+                path, first_line, ..
+            } => write!(f, "{path} listed again, first listed on line {first_line}"),
+            Anomaly::OutputWithoutListing { path, .. } => {
+                write!(f, "{path} appears without a `$ ls` before it")
+            }
+            Anomaly::ConflictingSize {
+                path,
+                size,
+                first_line,
+                first_size,
+                ..
+            } => write!(
+                f,
+                "{path} has size {size}, but had size {first_size} on line {first_line}"
+            ),
+            Anomaly::ConflictingKind {
+                path, first_line, ..
+            } => write!(
+                f,
+                "{path} is used as both file and directory, first on line {first_line}"
+            ),
+            Anomaly::UndeclaredDirectory { path, .. } => {
+                write!(f, "cd into {path}, which no listing declared")
+            }
+            Anomaly::UnvisitedDirectory { path, .. } => {
+                write!(f, "directory {path} is never listed, its size is unknown")
+            }
+        }
+    }
+}
+
+fn join(dir: &[String], name: &str) -> String {
+    format!("{}/{name}", dir.iter().map(|c| format!("/{c}")).join(""))
+}
+
+fn path_of(dir: &[String]) -> String {
+    if dir.is_empty() {
+        "/".to_string()
+    } else {
+        dir.iter().map(|c| format!("/{c}")).join("")
+    }
+}
+
+/// Replays a transcript without building a filesystem and collects every anomaly
+/// sorted by line. Only lines that can not be parsed at all are an error.
+pub fn validate_transcript<S: AsRef<str>>(
+    lines: impl IntoIterator<Item = S>,
+) -> Result<Vec<Anomaly>> {
+    let mut anomalies = Vec::new();
+    let mut cwd: Vec<String> = Vec::new();
+    // path -> line it was declared on and the size for files
+    let mut entries: HashMap<String, (usize, Option<usize>)> = HashMap::new();
+    let mut listed: HashMap<String, usize> = HashMap::new();
+    // directories entered without being declared, already reported
+    let mut undeclared: HashSet<String> = HashSet::new();
+    let mut listing = false;
+
+    for (idx, line) in lines.into_iter().enumerate() {
+        let line_no = idx + 1;
+        let spec: FSSpecLine = line
+            .as_ref()
+            .parse()
+            .with_context(|| format!("transcript line {line_no}"))?;
+        match spec {
+            FSSpecLine::ChangeDir(dir) => {
+                listing = false;
+                if dir.starts_with('/') {
+                    cwd.clear();
+                }
+                for component in dir.split('/').filter(|c| !c.is_empty()) {
+                    match component {
+                        "." => {}
+                        ".." => {
+                            cwd.pop();
+                        }
+                        name => {
+                            let path = join(&cwd, name);
+                            match entries.get(&path) {
+                                Some((_, None)) => {}
+                                Some((first_line, Some(_))) => {
+                                    anomalies.push(Anomaly::ConflictingKind {
+                                        line: line_no,
+                                        path: path.clone(),
+                                        first_line: *first_line,
+                                    })
+                                }
+                                None if undeclared.contains(&path) => {}
+                                None => {
+                                    anomalies.push(Anomaly::UndeclaredDirectory {
+                                        line: line_no,
+                                        path: path.clone(),
+                                    });
+                                    undeclared.insert(path);
+                                }
+                            }
+                            cwd.push(name.to_string());
+                        }
+                    }
+                }
+            }
+            FSSpecLine::Listing => {
+                let path = path_of(&cwd);
+                match listed.get(&path) {
+                    Some(first_line) => anomalies.push(Anomaly::DuplicateListing {
+                        line: line_no,
+                        path,
+                        first_line: *first_line,
+                    }),
+                    None => {
+                        listed.insert(path, line_no);
+                    }
+                }
+                listing = true;
+            }
+            FSSpecLine::Dir(ref name) | FSSpecLine::File(ref name, _) => {
+                let path = join(&cwd, name);
+                let size = match spec {
+                    FSSpecLine::File(_, size) => Some(size),
+                    _ => None,
+                };
+                if !listing {
+                    anomalies.push(Anomaly::OutputWithoutListing {
+                        line: line_no,
+                        path: path.clone(),
+                    });
+                }
+                match entries.get(&path) {
+                    None => {
+                        entries.insert(path, (line_no, size));
+                    }
+                    Some((_, None)) if size.is_none() => {}
+                    Some((first_line, Some(first_size))) if size.is_some() => {
+                        if size != Some(*first_size) {
+                            anomalies.push(Anomaly::ConflictingSize {
+                                line: line_no,
+                                path,
+                                size: size.unwrap_or_default(),
+                                first_line: *first_line,
+                                first_size: *first_size,
+                            });
+                        }
+                    }
+                    Some((first_line, _)) => anomalies.push(Anomaly::ConflictingKind {
+                        line: line_no,
+                        path,
+                        first_line: *first_line,
+                    }),
+                }
+            }
+        }
+    }
+
+    let visited: HashSet<&String> = listed.keys().collect();
+    anomalies.extend(
+        entries
+            .iter()
+            .filter(|(path, (_, size))| size.is_none() && !visited.contains(path))
+            .map(|(path, (line, _))| Anomaly::UnvisitedDirectory {
+                line: *line,
+                path: path.clone(),
+            }),
+    );
+    anomalies.sort_by_key(|anomaly| anomaly.line());
+    Ok(anomalies)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_clean_transcript() {
+        let transcript = super::super::test::EXAMPLE.lines();
+        assert_eq!(validate_transcript(transcript).unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn test_anomalies() {
+        let transcript = "$ cd /
+$ ls
+dir a
+10 b
+dir c
+$ cd a
+$ ls
+5 x
+$ cd ..
+$ ls
+dir a
+12 b
+$ cd b
+$ cd /
+$ cd nowhere
+7 y
+dir b
+$ cd /nowhere";
+        let anomalies = validate_transcript(transcript.lines()).unwrap();
+        assert_eq!(
+            anomalies,
+            vec![
+                Anomaly::UnvisitedDirectory {
+                    line: 5,
+                    path: "/c".to_string()
+                },
+                Anomaly::DuplicateListing {
+                    line: 10,
+                    path: "/".to_string(),
+                    first_line: 2
+                },
+                Anomaly::ConflictingSize {
+                    line: 12,
+                    path: "/b".to_string(),
+                    size: 12,
+                    first_line: 4,
+                    first_size: 10
+                },
+                Anomaly::ConflictingKind {
+                    line: 13,
+                    path: "/b".to_string(),
+                    first_line: 4
+                },
+                Anomaly::UndeclaredDirectory {
+                    line: 15,
+                    path: "/nowhere".to_string()
+                },
+                Anomaly::OutputWithoutListing {
+                    line: 16,
+                    path: "/nowhere/y".to_string()
+                },
+                Anomaly::OutputWithoutListing {
+                    line: 17,
+                    path: "/nowhere/b".to_string()
+                },
+                Anomaly::UnvisitedDirectory {
+                    line: 17,
+                    path: "/nowhere/b".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            anomalies[2].to_string(),
+            "line 12: /b has size 12, but had size 10 on line 4"
+        );
+        assert!(validate_transcript(["$ rm -rf /"]).is_err());
+    }
+}
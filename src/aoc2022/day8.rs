@@ -1,8 +1,8 @@
-use itertools::Itertools;
-use std::ops::Range;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use crate::{
-    matrix::{DynMatrix, Matrix},
+    image::{write_ppm, write_svg, Rgb},
+    matrix::{Direction, DynMatrix, Matrix},
     *,
 };
 
@@ -10,37 +10,19 @@ use super::{Reportable, TaskResult};
 
 #[tracing::instrument]
 pub async fn part1() -> Result<Reportable> {
-    let mut input = read_file_lines("aoc2022/day8.txt").await?;
-    let mut matrix_lines: Vec<Vec<u8>> = Vec::new();
-
-    while let Some(line) = input.next().await {
-        let line = line
-            .chars()
-            .map(|x| x.to_digit(10).expect("unexpected character"))
-            .map(|digit| {
-                assert!(digit < 10);
-                digit as u8
-            });
-        matrix_lines.push(line.collect());
-    }
-
-    let matrix: TreeMatrix = matrix_lines.into();
+    let input: Vec<String> = read_file_lines("aoc2022/day8.txt").await?.collect().await;
+    let matrix = TreeMatrix::from_lines(input)?;
 
     trace!("Read in matrix: {matrix:#?}");
 
-    let mut num_long_trees = 0;
-    for x in 1..matrix.size_x() - 1 {
-        for y in 1..matrix.size_y() - 1 {
-            if matrix.only_shorter_any_dirs(x, y) {
-                num_long_trees += 1;
-            }
-        }
-    }
+    let num_long_trees = matrix.analyze().visible_count();
 
-    assert!(num_long_trees > 0);
+    trace!(
+        "Visible trees:\n{}",
+        matrix.render_heatmap(Heatmap::Visibility)
+    );
 
-    num_long_trees += matrix.size_x() * 2;
-    num_long_trees += matrix.size_y() * 2 - 4;
+    assert!(num_long_trees > 0);
 
     Ok(Reportable {
         year: 2022,
@@ -52,33 +34,14 @@ pub async fn part1() -> Result<Reportable> {
 
 #[tracing::instrument]
 pub async fn part2() -> Result<Reportable> {
-    let mut input = read_file_lines("aoc2022/day8.txt").await?;
-    let mut matrix_lines: Vec<Vec<u8>> = Vec::new();
-
-    while let Some(line) = input.next().await {
-        let line = line
-            .chars()
-            .map(|x| x.to_digit(10).expect("unexpected character"))
-            .map(|digit| {
-                assert!(digit < 10);
-                digit as u8
-            });
-        matrix_lines.push(line.collect());
-    }
-
-    let matrix: TreeMatrix = matrix_lines.into();
+    let input: Vec<String> = read_file_lines("aoc2022/day8.txt").await?.collect().await;
+    let matrix = TreeMatrix::from_lines(input)?;
 
     trace!("Read in matrix: {matrix:#?}");
 
-    let mut best_scenic = 0;
-    for x in 1..matrix.size_x() - 1 {
-        for y in 1..matrix.size_y() - 1 {
-            let ss = matrix.scenic_score(x, y);
-            if ss > best_scenic {
-                best_scenic = ss;
-            }
-        }
-    }
+    let best_scenic = matrix.analyze().best_scenic_score();
+
+    trace!("Scenic scores:\n{}", matrix.render_heatmap(Heatmap::Scenic));
 
     assert!(best_scenic > 0);
 
@@ -114,125 +77,253 @@ impl From<Vec<Vec<u8>>> for TreeMatrix {
 }
 
 impl TreeMatrix {
-    /// returns true if all
-    fn only_shorter(&self, x: usize, y: usize, dir: Direction) -> bool {
-        let (tree_count, rem_trees, blocked) = self.tree_visible_count(x, y, dir);
-        trace!("{dir:?}: Tree at {x} {y} has {tree_count} visible trees");
-        if blocked {
-            trace!("Has {rem_trees} non-visible trees, visible from edge");
-            !blocked
-        } else {
-            trace!("Has {rem_trees} non-visible trees, not visible from edge");
-            !blocked
-        }
+    /// Parses a grid of tree heights, one digit per tree
+    pub fn from_lines<S: AsRef<str>>(lines: impl IntoIterator<Item = S>) -> Result<Self> {
+        DynMatrix::from_grid(lines, |c| {
+            c.to_digit(10)
+                .map(|digit| digit as u8)
+                .ok_or_else(|| report!("unexpected character {c:?}"))
+        })
+        .map(TreeMatrix)
     }
 
-    fn scenic_score(&self, x: usize, y: usize) -> usize {
-        self.tree_visible_count(x, y, Direction::Up).0
-            * self.tree_visible_count(x, y, Direction::Down).0
-            * self.tree_visible_count(x, y, Direction::Left).0
-            * self.tree_visible_count(x, y, Direction::Right).0
+    /// Generates a forest of random tree heights for stress testing
+    pub fn random(size_x: usize, size_y: usize, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        TreeMatrix(DynMatrix::from_fn(size_x, size_y, |_, _| {
+            rng.gen_range(0..10)
+        }))
     }
 
-    fn tree_visible_count(&self, x: usize, y: usize, dir: Direction) -> (usize, usize, bool) {
-        let tree_size = *self.get(x, y).unwrap();
-        let xo = x;
-        let yo = y;
-        let posi = dir.to_pos_iter(self, x, y);
-        let mut tree_count: usize = 0;
-        let mut stopped_count_at = None;
-        let mut last_block_status = false;
-        for (x, y) in posi {
-            trace!("Checking position {x}/{y} against {xo}/{yo} tree size");
-            let this_tree_size = *self.get(x, y).unwrap();
-            tree_count += 1;
-            if this_tree_size >= tree_size {
-                if stopped_count_at.is_none() {
-                    last_block_status = true;
-                    trace!("{dir:?}: Tree at {x} {y} is longer ({this_tree_size}) than Tree at {xo} {yo} ({tree_size}), shadowed at {tree_count} trees in this range");
-                    stopped_count_at = Some(tree_count);
+    /// Computes the sightlines of every tree in all four directions.
+    ///
+    /// Each direction is a single sweep per row or column that keeps a stack of
+    /// the trees that are still in view, from the edge being looked at, with
+    /// non-increasing heights. Trees smaller than the current one can never
+    /// block the view of any later tree and are dropped, so the remaining top of
+    /// the stack is the nearest blocking tree.
+    pub fn analyze(&self) -> ForestView {
+        let mut views = DynMatrix::filled(self.size_x(), self.size_y(), TreeView::default());
+        if self.is_empty() {
+            return ForestView { views };
+        }
+        let mut stack: Vec<(usize, u8)> = Vec::new();
+        for dir in Direction::CARDINAL {
+            let edge: Vec<(usize, usize)> = match dir {
+                Direction::Up => (0..self.size_y()).map(|y| (0, y)).collect(),
+                Direction::Down => (0..self.size_y()).map(|y| (self.size_x() - 1, y)).collect(),
+                Direction::Left => (0..self.size_x()).map(|x| (x, 0)).collect(),
+                Direction::Right => (0..self.size_x()).map(|x| (x, self.size_y() - 1)).collect(),
+                _ => unreachable!("only cardinal directions are swept"),
+            };
+            for (x, y) in edge {
+                stack.clear();
+                let line = std::iter::once((x, y)).chain(self.ray(x, y, dir.opposite()));
+                for (pos, (x, y)) in line.enumerate() {
+                    let height = self[(x, y)];
+                    while matches!(stack.last(), Some((_, top)) if *top < height) {
+                        stack.pop();
+                    }
+                    views[(x, y)].set(
+                        dir,
+                        match stack.last() {
+                            None => Sightline {
+                                distance: pos,
+                                blocked: false,
+                            },
+                            Some((blocker, _)) => Sightline {
+                                distance: pos - blocker,
+                                blocked: true,
+                            },
+                        },
+                    );
+                    stack.push((pos, height));
                 }
-            } else if stopped_count_at.is_none() {
-                last_block_status = false;
             }
         }
-        trace!("{dir:?}: Tree at {x} {y} has {tree_count} visible trees");
-        if stopped_count_at.is_none() {
-            stopped_count_at = Some(tree_count);
-        }
-        let stopped_count_at = stopped_count_at.unwrap();
-        let rem_tree = tree_count - stopped_count_at;
-        (stopped_count_at, rem_tree, last_block_status)
+        ForestView { views }
     }
 
-    pub fn only_shorter_any_dirs(&self, x: usize, y: usize) -> bool {
-        self.only_shorter(x, y, Direction::Up)
-            || self.only_shorter(x, y, Direction::Down)
-            || self.only_shorter(x, y, Direction::Right)
-            || self.only_shorter(x, y, Direction::Left)
+    /// Walks from a single tree towards the edge, used to cross-check [`TreeMatrix::analyze`]
+    pub fn sightline(&self, x: usize, y: usize, dir: Direction) -> Sightline {
+        let tree_size = self[(x, y)];
+        let mut distance = 0;
+        for (bx, by) in self.ray(x, y, dir) {
+            distance += 1;
+            if self[(bx, by)] >= tree_size {
+                trace!("{dir:?}: Tree at {x} {y} is shadowed by tree at {bx} {by}");
+                return Sightline {
+                    distance,
+                    blocked: true,
+                };
+            }
+        }
+        Sightline {
+            distance,
+            blocked: false,
+        }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub enum Direction {
-    Up,
-    Down,
-    Right,
-    Left,
+/// A per-tree value that can be exported as a heatmap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Heatmap {
+    /// Trees that are visible from outside the forest
+    Visibility,
+    /// Scenic scores, log scaled to the best score in the forest
+    Scenic,
+    /// Trees where [`TreeMatrix::analyze`] disagrees with [`TreeMatrix::sightline`]
+    Mismatch,
 }
 
-impl Direction {
-    pub fn to_pos_iter(
-        &self,
-        tree: &TreeMatrix,
-        x: usize,
-        y: usize,
-    ) -> impl Iterator<Item = (usize, usize)> {
-        let size_x = tree.size_x();
-        let size_y = tree.size_y();
-        const fn dead_range_at(t: usize) -> Range<usize> {
-            t..t + 1
+/// Text heatmap shades from cold to hot
+const HEAT_SHADES: [char; 10] = [' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'];
+const HEAT_COLD: Rgb = Rgb(16, 32, 64);
+const HEAT_HOT: Rgb = Rgb(255, 224, 0);
+
+impl TreeMatrix {
+    /// Returns the heat of every tree between 0 and 1
+    fn heat(&self, heatmap: Heatmap) -> DynMatrix<f64> {
+        let forest = self.analyze();
+        let flag = |hot: bool| if hot { 1.0 } else { 0.0 };
+        match heatmap {
+            Heatmap::Visibility => forest.views().map(|view| flag(view.visible())),
+            Heatmap::Scenic => {
+                let best = (forest.best_scenic_score() as f64).ln_1p();
+                forest.views().map(|view| {
+                    if best > 0.0 {
+                        (view.scenic_score() as f64).ln_1p() / best
+                    } else {
+                        0.0
+                    }
+                })
+            }
+            Heatmap::Mismatch => DynMatrix::from_fn(self.size_x(), self.size_y(), |x, y| {
+                flag(
+                    Direction::CARDINAL
+                        .into_iter()
+                        .any(|dir| forest.view(x, y).get(dir) != self.sightline(x, y, dir)),
+                )
+            }),
         }
+    }
 
-        fn range_pair_to_iter<
-            I: IntoIterator<Item = usize>,
-            I2: IntoIterator<Item = usize> + Clone,
-        >(
-            r: I,
-            r2: I2,
-        ) -> Vec<(usize, usize)>
-        where
-            <I2 as IntoIterator>::IntoIter: Clone + 'static,
-        {
-            let pos = r.into_iter().cartesian_product(r2);
-            pos.collect_vec()
+    /// Renders the heatmap as text, one character per tree
+    pub fn render_heatmap(&self, heatmap: Heatmap) -> String {
+        self.heat(heatmap)
+            .render(|heat| HEAT_SHADES[(heat * 9.0).round() as usize])
+    }
+
+    /// Colors every tree by its heat
+    pub fn heatmap_image(&self, heatmap: Heatmap) -> DynMatrix<Rgb> {
+        self.heat(heatmap)
+            .map(|heat| HEAT_COLD.lerp(HEAT_HOT, *heat))
+    }
+
+    /// Writes the heatmap as a PPM image with `scale` pixels per tree
+    pub fn write_heatmap_ppm<W: std::io::Write>(
+        &self,
+        heatmap: Heatmap,
+        scale: usize,
+        out: &mut W,
+    ) -> Result<()> {
+        write_ppm(&self.heatmap_image(heatmap), scale, out).context("writing PPM heatmap")
+    }
+
+    /// Writes the heatmap as an SVG image with `scale` pixels per tree
+    pub fn write_heatmap_svg<W: std::io::Write>(
+        &self,
+        heatmap: Heatmap,
+        scale: usize,
+        out: &mut W,
+    ) -> Result<()> {
+        write_svg(&self.heatmap_image(heatmap), scale, out).context("writing SVG heatmap")
+    }
+}
+
+/// What can be seen from a tree looking in one direction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sightline {
+    /// Number of trees in view, up to and including the first tree that is at least as tall
+    pub distance: usize,
+    /// True if a tree at least as tall hides this tree from the edge
+    pub blocked: bool,
+}
+
+/// The sightlines of a tree in all four directions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TreeView {
+    pub up: Sightline,
+    pub down: Sightline,
+    pub left: Sightline,
+    pub right: Sightline,
+}
+
+impl TreeView {
+    pub fn get(&self, dir: Direction) -> Sightline {
+        match dir {
+            Direction::Up => self.up,
+            Direction::Down => self.down,
+            Direction::Left => self.left,
+            Direction::Right => self.right,
+            _ => panic!("trees are only viewed in cardinal directions"),
         }
+    }
 
-        match self {
-            Direction::Up => range_pair_to_iter((0..x).rev(), dead_range_at(y)).into_iter(),
-            Direction::Down => range_pair_to_iter(x + 1..size_x, dead_range_at(y)).into_iter(),
-            Direction::Right => range_pair_to_iter(dead_range_at(x), y + 1..size_y).into_iter(),
-            Direction::Left => range_pair_to_iter(dead_range_at(x), (0..y).rev()).into_iter(),
+    fn set(&mut self, dir: Direction, sightline: Sightline) {
+        match dir {
+            Direction::Up => self.up = sightline,
+            Direction::Down => self.down = sightline,
+            Direction::Left => self.left = sightline,
+            Direction::Right => self.right = sightline,
+            _ => panic!("trees are only viewed in cardinal directions"),
         }
     }
+
+    /// Returns true if the tree can be seen from outside the forest
+    pub fn visible(&self) -> bool {
+        !(self.up.blocked && self.down.blocked && self.left.blocked && self.right.blocked)
+    }
+
+    pub fn scenic_score(&self) -> usize {
+        self.up.distance * self.down.distance * self.left.distance * self.right.distance
+    }
+}
+
+/// The views of all trees in a forest, see [`TreeMatrix::analyze`]
+pub struct ForestView {
+    views: DynMatrix<TreeView>,
+}
+
+impl ForestView {
+    /// SAFETY
+    ///
+    /// Panics when X or Y are out of bounds
+    pub fn view(&self, x: usize, y: usize) -> &TreeView {
+        &self.views[(x, y)]
+    }
+
+    pub fn views(&self) -> &DynMatrix<TreeView> {
+        &self.views
+    }
+
+    /// Returns the number of trees visible from outside the forest
+    pub fn visible_count(&self) -> usize {
+        self.views.cells().filter(|view| view.visible()).count()
+    }
+
+    pub fn best_scenic_score(&self) -> usize {
+        self.views
+            .cells()
+            .map(|view| view.scenic_score())
+            .max()
+            .unwrap_or(0)
+    }
 }
 
 #[cfg(test)]
 fn test_matrix() -> TreeMatrix {
-    let data = vec!["30373", "25512", "65332", "33549", "35390"];
-
-    let mut data_out = Vec::new();
-    for line in data {
-        let line = line
-            .chars()
-            .map(|x| x.to_digit(10).expect("unexpected character"))
-            .map(|digit| {
-                assert!(digit < 10);
-                digit as u8
-            });
-        data_out.push(line.collect_vec());
-    }
-    data_out.into()
+    TreeMatrix::from_lines(["30373", "25512", "65332", "33549", "35390"]).unwrap()
 }
 
 #[cfg(test)]
@@ -240,42 +331,128 @@ fn test_matrix() -> TreeMatrix {
 #[tracing_test::traced_test]
 pub fn dir_check_test() {
     let tmat = test_matrix();
+    let forest = tmat.analyze();
 
-    assert_eq!(tmat.tree_visible_count(1, 2, Direction::Up), (1, 0, false));
+    assert_eq!(
+        tmat.sightline(1, 2, Direction::Up),
+        Sightline {
+            distance: 1,
+            blocked: false
+        }
+    );
 
-    assert!(tmat.only_shorter(1, 1, Direction::Up));
-    assert!(tmat.only_shorter(1, 1, Direction::Left));
-    assert!(!tmat.only_shorter(1, 1, Direction::Down));
-    assert!(!tmat.only_shorter(1, 1, Direction::Right));
+    assert!(!forest.view(1, 1).up.blocked);
+    assert!(!forest.view(1, 1).left.blocked);
+    assert!(forest.view(1, 1).down.blocked);
+    assert!(forest.view(1, 1).right.blocked);
 
-    assert!(tmat.only_shorter(1, 2, Direction::Up));
-    assert!(tmat.only_shorter(1, 2, Direction::Right));
-    assert!(!tmat.only_shorter(1, 2, Direction::Left));
-    assert!(!tmat.only_shorter(1, 2, Direction::Down));
+    assert!(!forest.view(1, 2).up.blocked);
+    assert!(!forest.view(1, 2).right.blocked);
+    assert!(forest.view(1, 2).left.blocked);
+    assert!(forest.view(1, 2).down.blocked);
 
-    assert_eq!(tmat.tree_visible_count(3, 2, Direction::Up), (2, 1, true));
     assert_eq!(
-        tmat.tree_visible_count(3, 2, Direction::Left),
-        (2, 0, false)
+        *forest.view(3, 2),
+        TreeView {
+            up: Sightline {
+                distance: 2,
+                blocked: true
+            },
+            left: Sightline {
+                distance: 2,
+                blocked: false
+            },
+            down: Sightline {
+                distance: 1,
+                blocked: false
+            },
+            right: Sightline {
+                distance: 2,
+                blocked: true
+            },
+        }
     );
+
+    assert_eq!(forest.view(3, 2).scenic_score(), 8);
+
+    for dir in Direction::CARDINAL {
+        assert_eq!(
+            forest.view(2, 2).get(dir),
+            Sightline {
+                distance: 1,
+                blocked: true
+            }
+        );
+    }
+
+    assert_eq!(forest.view(2, 2).scenic_score(), 1);
+
+    assert_eq!(forest.visible_count(), 21);
+    assert_eq!(forest.best_scenic_score(), 8);
+}
+
+#[cfg(test)]
+#[test]
+pub fn test_sweep_matches_walk() {
+    for (size_x, size_y, seed) in [(1, 1, 0), (1, 7, 1), (9, 1, 2), (37, 53, 3)] {
+        let tmat = TreeMatrix::random(size_x, size_y, seed);
+        let forest = tmat.analyze();
+        for x in 0..size_x {
+            for y in 0..size_y {
+                for dir in Direction::CARDINAL {
+                    assert_eq!(forest.view(x, y).get(dir), tmat.sightline(x, y, dir));
+                }
+            }
+        }
+    }
     assert_eq!(
-        tmat.tree_visible_count(3, 2, Direction::Down),
-        (1, 0, false)
+        TreeMatrix::from_lines([""; 0])
+            .unwrap()
+            .analyze()
+            .visible_count(),
+        0
     );
+}
+
+#[cfg(test)]
+#[test]
+pub fn test_heatmap() {
+    let tmat = test_matrix();
     assert_eq!(
-        tmat.tree_visible_count(3, 2, Direction::Right),
-        (2, 0, true)
+        tmat.render_heatmap(Heatmap::Visibility),
+        "@@@@@\n@@@ @\n@@ @@\n@ @ @\n@@@@@"
     );
-
-    assert_eq!(tmat.scenic_score(3, 2), 8);
-
-    assert_eq!(tmat.tree_visible_count(2, 2, Direction::Up), (1, 1, true));
-    assert_eq!(tmat.tree_visible_count(2, 2, Direction::Left), (1, 1, true));
-    assert_eq!(tmat.tree_visible_count(2, 2, Direction::Down), (1, 1, true));
+    let scenic = tmat.render_heatmap(Heatmap::Scenic);
+    assert_eq!(scenic.lines().nth(3).unwrap(), " -@* ");
+    assert_eq!(scenic.lines().next().unwrap(), "     ");
+    assert!(tmat
+        .render_heatmap(Heatmap::Mismatch)
+        .chars()
+        .all(|c| c == ' ' || c == '\n'));
+
+    let image = tmat.heatmap_image(Heatmap::Scenic);
+    assert_eq!(image[(3, 2)], HEAT_HOT);
+    assert_eq!(image[(0, 0)], HEAT_COLD);
+
+    let mut ppm = Vec::new();
+    tmat.write_heatmap_ppm(Heatmap::Visibility, 2, &mut ppm)
+        .unwrap();
+    assert!(ppm.starts_with(b"P6\n10 10\n255\n"));
+    let mut svg = Vec::new();
+    tmat.write_heatmap_svg(Heatmap::Scenic, 4, &mut svg)
+        .unwrap();
     assert_eq!(
-        tmat.tree_visible_count(2, 2, Direction::Right),
-        (1, 1, true)
+        String::from_utf8(svg).unwrap().matches("<rect ").count(),
+        25
     );
+}
 
-    assert_eq!(tmat.scenic_score(2, 2), 1);
+#[cfg(test)]
+#[test]
+pub fn test_stress_grid() {
+    // 10x the side length of the puzzle input
+    let tmat = TreeMatrix::random(990, 990, 8);
+    let forest = tmat.analyze();
+    assert!(forest.visible_count() >= 4 * 989);
+    assert!(forest.best_scenic_score() > 0);
 }
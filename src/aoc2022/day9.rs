@@ -1,8 +1,7 @@
 use color_eyre::Report;
-use itertools::Itertools;
 use std::str::FromStr;
 
-use crate::*;
+use crate::{matrix::SparseMatrix, *};
 
 use super::{Reportable, TaskResult};
 
@@ -22,14 +21,17 @@ pub async fn part1() -> Result<Reportable> {
         rope.move_head(mov);
     }
 
-    let pos_list = rope.tail_pos.clone();
-    let pos_list = pos_list.into_iter().unique().collect_vec();
+    trace!(
+        "Tail positions:\n{}",
+        rope.tail_pos
+            .render(|pos| if pos.is_some() { '#' } else { '.' })
+    );
 
     Ok(Reportable {
         year: 2022,
         day: 9,
         part: 1.into(),
-        result: TaskResult::Usize(pos_list.len() + 1),
+        result: TaskResult::Usize(rope.tail_pos.len() + 1),
     })
 }
 
@@ -50,14 +52,13 @@ pub async fn part2() -> Result<Reportable> {
         rope.move_head_rec(mov, rrope);
     }
 
-    let pos_list = rope.last().unwrap().tail_pos.clone();
-    let pos_list = pos_list.into_iter().unique().collect_vec();
+    let tail_pos = &rope.last().unwrap().tail_pos;
 
     Ok(Reportable {
         year: 2022,
         day: 9,
         part: 2.into(),
-        result: TaskResult::Usize(pos_list.len() + 1),
+        result: TaskResult::Usize(tail_pos.len() + 1),
     })
 }
 
@@ -66,7 +67,7 @@ pub struct Rope {
     head: (isize, isize),
     prev_head: (isize, isize),
     tail: (isize, isize),
-    tail_pos: Vec<(isize, isize)>,
+    tail_pos: SparseMatrix<()>,
 }
 
 impl std::fmt::Debug for Rope {
@@ -157,16 +158,11 @@ impl Rope {
         let td = thd.into_adj();
         trace!("Computed thd adj delta {td:?}");
         if td.0 != 0 || td.1 != 0 {
-            if !self.tail_pos.contains(&self.tail) {
-                self.tail_pos.push(self.tail);
-            }
+            let prev_tail = self.tail;
+            self.tail_pos.set(prev_tail.0, prev_tail.1, ());
             self.tail.0 += td.0;
             self.tail.1 += td.1;
-            trace!(
-                "Adjusting Tail: {:?} -> {:?}",
-                self.tail_pos.last().unwrap(),
-                self.tail
-            );
+            trace!("Adjusting Tail: {prev_tail:?} -> {:?}", self.tail);
             Some(Move::from_xy((td.0, td.1)))
         } else {
             None
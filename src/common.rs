@@ -1,3 +1,4 @@
+pub mod image;
 pub mod matrix;
 
 use std::{borrow::Cow, pin::Pin};
@@ -9,21 +10,86 @@ pub use rayon::prelude::*;
 pub use tokio_stream::StreamExt;
 pub use tracing::{debug, error, info, trace, warn};
 
-use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+use async_compression::tokio::bufread::{GzipDecoder, ZstdDecoder};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt};
 use tokio_stream::Stream;
 
+/// Filename that reads the input from standard input instead of a file
+pub const STDIN_INPUT: &str = "-";
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Opens an input for buffered reading.
+///
+/// Reads from standard input if the filename is [`STDIN_INPUT`]. Gzip and zstd
+/// compressed inputs are recognized by their magic bytes and decompressed transparently.
+#[tracing::instrument]
+pub async fn open_input(filename: &str) -> Result<Pin<Box<dyn AsyncBufRead + Send>>> {
+    let input: Pin<Box<dyn AsyncRead + Send>> = if filename == STDIN_INPUT {
+        Box::pin(tokio::io::stdin())
+    } else {
+        let file = tokio::fs::File::open(filename)
+            .await
+            .with_context(|| format!("opening input {filename:?}"))?;
+        Box::pin(file)
+    };
+    decompress(filename, input).await
+}
+
+/// Looks at the first bytes of the input and wraps it in a decoder if they are
+/// the magic bytes of a compression format
+async fn decompress(
+    filename: &str,
+    mut input: Pin<Box<dyn AsyncRead + Send>>,
+) -> Result<Pin<Box<dyn AsyncBufRead + Send>>> {
+    // pipes may return fewer bytes than the longest magic on a single read
+    let mut magic = vec![0; ZSTD_MAGIC.len()];
+    let mut len = 0;
+    while len < magic.len() {
+        let read = input
+            .read(&mut magic[len..])
+            .await
+            .with_context(|| format!("reading input {filename:?}"))?;
+        if read == 0 {
+            break;
+        }
+        len += read;
+    }
+    magic.truncate(len);
+    let input = tokio::io::BufReader::new(std::io::Cursor::new(magic.clone()).chain(input));
+    Ok(if magic.starts_with(GZIP_MAGIC) {
+        trace!("Input {filename:?} is gzip compressed");
+        let mut decoder = GzipDecoder::new(input);
+        decoder.multiple_members(true);
+        Box::pin(tokio::io::BufReader::new(decoder))
+    } else if magic.starts_with(ZSTD_MAGIC) {
+        trace!("Input {filename:?} is zstd compressed");
+        let mut decoder = ZstdDecoder::new(input);
+        decoder.multiple_members(true);
+        Box::pin(tokio::io::BufReader::new(decoder))
+    } else {
+        Box::pin(input)
+    })
+}
+
+/// Streams the lines of an input.
+///
+/// Errors while opening the input are returned, read errors in the middle of
+/// the input, like a truncated archive, panic with the filename.
 #[tracing::instrument]
 pub async fn read_file_lines(filename: &str) -> Result<Pin<Box<dyn Stream<Item = String> + Send>>> {
-    let file = tokio::fs::File::open(filename).await?;
-    let lines = tokio::io::BufReader::new(file);
+    let lines = open_input(filename).await?;
     let lines = tokio_stream::wrappers::LinesStream::new(lines.lines());
-    Ok(Box::pin(lines.map(|f| f.unwrap())))
+    let filename = filename.to_string();
+    Ok(Box::pin(lines.map(move |line| {
+        line.unwrap_or_else(|err| panic!("reading input {filename:?}: {err}"))
+    })))
 }
 
 #[tracing::instrument]
 pub async fn read_file_chars<'a>(filename: &str) -> Result<Cow<'a, [u8]>> {
-    let file = tokio::fs::File::open(filename).await?;
-    let mut chars = tokio::io::BufReader::new(file);
+    let mut chars = open_input(filename).await?;
     let mut buf = Vec::new();
     chars.read_to_end(&mut buf).await?;
     let buf = Cow::from(buf);
@@ -142,6 +208,63 @@ pub struct TaskConfig {
     /// Which parts to run. If this is specified without day, runs the given parts of all days
     #[arg(short, long)]
     pub(crate) part: Option<TaskPart>,
+    /// Run a tool instead of the puzzle solutions
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>,
+}
+
+#[derive(clap::Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Replay a day7 shell transcript and print the reconstructed filesystem
+    Fs {
+        /// Transcript to replay, `-` reads from stdin
+        #[arg(default_value = "aoc2022/day7.txt")]
+        transcript: String,
+        /// Treat the input as a directory on the host and import it instead of replaying it
+        #[arg(long)]
+        host: bool,
+        /// Print the canonical transcript of the filesystem instead of a tree
+        #[arg(long, conflicts_with = "du")]
+        emit_transcript: bool,
+        /// Print directory totals like `du -h`, largest first, instead of a tree
+        #[arg(long)]
+        du: bool,
+        /// Do not descend more than this many levels below the root
+        #[arg(long)]
+        max_depth: Option<usize>,
+    },
+    /// Step through a day10 program, reading debugger commands from stdin
+    Debug {
+        /// Program to load, stdin is taken by the debugger commands
+        #[arg(default_value = "aoc2022/day10.txt")]
+        program: String,
+    },
+    /// Run a day10 program and save what it draws on the CRT as an image
+    Crt {
+        /// Program to run, `-` reads from stdin
+        #[arg(default_value = "aoc2022/day10.txt")]
+        program: String,
+        /// Image file to write, or the directory for the frames with `--frames-every`
+        #[arg(long, short)]
+        output: String,
+        #[arg(long, value_enum, default_value_t = image::ImageFormat::Ppm)]
+        format: image::ImageFormat,
+        /// Image pixels per screen pixel
+        #[arg(long, default_value_t = 8, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        scale: usize,
+        /// Write a frame after every this many cycles instead of only the final screen
+        #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<u128>::new().range(1..))]
+        frames_every: Option<u128>,
+        /// Screen width in pixels
+        #[arg(long, default_value_t = 40)]
+        width: usize,
+        /// Screen height in pixels
+        #[arg(long, default_value_t = 6)]
+        height: usize,
+        /// Pixels covered by the sprite
+        #[arg(long, default_value_t = 3)]
+        sprite_width: usize,
+    },
 }
 
 impl TaskConfig {
@@ -159,4 +282,133 @@ impl TaskConfig {
         };
         is_day && is_part
     }
+
+    pub fn command(&self) -> Option<&Command> {
+        self.command.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use async_compression::tokio::write::{GzipEncoder, ZstdEncoder};
+    use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+    use super::*;
+
+    const TEST_INPUT: &str = "first line\nsecond line\n\nfourth line\n";
+
+    async fn write_input<W: AsyncWrite + Unpin>(mut encoder: W) -> W {
+        encoder.write_all(TEST_INPUT.as_bytes()).await.unwrap();
+        encoder.shutdown().await.unwrap();
+        encoder
+    }
+
+    async fn check_input(name: &str, data: &[u8]) {
+        let path = std::env::temp_dir().join(format!("aoc-{}-{name}", std::process::id()));
+        tokio::fs::write(&path, data).await.unwrap();
+        let path = path.to_str().unwrap();
+
+        let lines: Vec<String> = read_file_lines(path).await.unwrap().collect().await;
+        let chars = read_file_chars(path).await.unwrap().into_owned();
+        tokio::fs::remove_file(path).await.unwrap();
+
+        assert_eq!(lines, vec!["first line", "second line", "", "fourth line"]);
+        assert_eq!(chars, TEST_INPUT.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_plain_input() {
+        check_input("plain.txt", TEST_INPUT.as_bytes()).await;
+    }
+
+    #[tokio::test]
+    async fn test_gzip_input() {
+        let data = write_input(GzipEncoder::new(Vec::new())).await.into_inner();
+        check_input("input.txt.gz", &data).await;
+    }
+
+    #[tokio::test]
+    async fn test_zstd_input() {
+        let data = write_input(ZstdEncoder::new(Vec::new())).await.into_inner();
+        check_input("input.txt.zst", &data).await;
+    }
+
+    /// Hands out the data one byte per read, like a slow pipe
+    struct Trickle(Vec<u8>);
+
+    impl AsyncRead for Trickle {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            if !self.0.is_empty() {
+                let byte = self.0.remove(0);
+                buf.put_slice(&[byte]);
+            }
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_trickled_input() {
+        let gzip = write_input(GzipEncoder::new(Vec::new())).await.into_inner();
+        let zstd = write_input(ZstdEncoder::new(Vec::new())).await.into_inner();
+        for data in [TEST_INPUT.as_bytes().to_vec(), gzip, zstd, b"ab".to_vec()] {
+            let mut input = decompress("trickle", Box::pin(Trickle(data.clone())))
+                .await
+                .unwrap();
+            let mut buf = Vec::new();
+            input.read_to_end(&mut buf).await.unwrap();
+            let expected = if data == b"ab" {
+                b"ab".as_slice()
+            } else {
+                TEST_INPUT.as_bytes()
+            };
+            assert_eq!(buf, expected);
+        }
+    }
+
+    async fn truncated_gzip(name: &str) -> String {
+        let data = write_input(GzipEncoder::new(Vec::new())).await.into_inner();
+        let path = std::env::temp_dir().join(format!("aoc-{}-{name}", std::process::id()));
+        tokio::fs::write(&path, &data[..data.len() / 2])
+            .await
+            .unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_truncated_input_chars() {
+        let path = truncated_gzip("truncated-chars.gz").await;
+        let chars = read_file_chars(&path).await;
+        tokio::fs::remove_file(&path).await.unwrap();
+        assert!(chars.is_err());
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "truncated-lines.gz")]
+    async fn test_truncated_input_lines() {
+        let path = truncated_gzip("truncated-lines.gz").await;
+        let lines = read_file_lines(&path).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let _: Vec<String> = lines.collect().await;
+    }
+
+    #[test]
+    fn test_crt_arguments() {
+        use clap::Parser;
+        let parse = |args: &[&str]| {
+            TaskConfig::try_parse_from(["aoc", "crt", "-o", "out"].iter().chain(args))
+        };
+        assert!(parse(&[]).is_ok());
+        assert!(parse(&["--scale", "2", "--frames-every", "40"]).is_ok());
+        assert!(parse(&["--scale", "0"]).is_err());
+        assert!(parse(&["--frames-every", "0"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_missing_input() {
+        assert!(read_file_lines("aoc2022/does_not_exist.txt").await.is_err());
+    }
 }
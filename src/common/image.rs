@@ -0,0 +1,185 @@
+//! Writers for simple image formats, rendering every cell of a [`Matrix`] as a
+//! square of `scale` by `scale` pixels. X maps to image rows and Y to columns.
+
+use std::io::Write;
+
+use crate::matrix::Matrix;
+
+/// A 24-bit color
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb(0, 0, 0);
+    pub const WHITE: Rgb = Rgb(255, 255, 255);
+
+    /// Blends linearly from this color to the other, T is clamped to 0..=1
+    pub fn lerp(self, other: Rgb, t: f64) -> Rgb {
+        let t = t.clamp(0.0, 1.0);
+        let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+        Rgb(
+            mix(self.0, other.0),
+            mix(self.1, other.1),
+            mix(self.2, other.2),
+        )
+    }
+
+    /// Formats the color as `#rrggbb`
+    pub fn hex(self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+/// Image formats the writers in this module produce
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ImageFormat {
+    Pbm,
+    Ppm,
+    Svg,
+}
+
+impl ImageFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Pbm => "pbm",
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Svg => "svg",
+        }
+    }
+}
+
+/// Writes a binary PBM (P4) image, set cells are black
+pub fn write_pbm<M, W>(matrix: &M, scale: usize, out: &mut W) -> std::io::Result<()>
+where
+    M: Matrix<Data = bool> + ?Sized,
+    W: Write + ?Sized,
+{
+    write!(
+        out,
+        "P4\n{} {}\n",
+        matrix.size_y() * scale,
+        matrix.size_x() * scale
+    )?;
+    let mut bits = Vec::with_capacity(matrix.size_y() * scale);
+    for x in 0..matrix.size_x() {
+        bits.clear();
+        for set in matrix.row_iter(x) {
+            bits.extend(std::iter::repeat_n(*set, scale));
+        }
+        // rows are padded to whole bytes
+        let line: Vec<u8> = bits
+            .chunks(8)
+            .map(|byte| {
+                byte.iter()
+                    .enumerate()
+                    .fold(0, |acc, (bit, set)| acc | (*set as u8) << (7 - bit))
+            })
+            .collect();
+        for _ in 0..scale {
+            out.write_all(&line)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes a binary PPM (P6) image
+pub fn write_ppm<M, W>(matrix: &M, scale: usize, out: &mut W) -> std::io::Result<()>
+where
+    M: Matrix<Data = Rgb> + ?Sized,
+    W: Write + ?Sized,
+{
+    write!(
+        out,
+        "P6\n{} {}\n255\n",
+        matrix.size_y() * scale,
+        matrix.size_x() * scale
+    )?;
+    let mut line = Vec::with_capacity(matrix.size_y() * scale * 3);
+    for x in 0..matrix.size_x() {
+        line.clear();
+        for Rgb(r, g, b) in matrix.row_iter(x) {
+            for _ in 0..scale {
+                line.extend_from_slice(&[*r, *g, *b]);
+            }
+        }
+        for _ in 0..scale {
+            out.write_all(&line)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes an SVG image with one square per cell
+pub fn write_svg<M, W>(matrix: &M, scale: usize, out: &mut W) -> std::io::Result<()>
+where
+    M: Matrix<Data = Rgb> + ?Sized,
+    W: Write + ?Sized,
+{
+    let (width, height) = (matrix.size_y() * scale, matrix.size_x() * scale);
+    writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}" shape-rendering="crispEdges">"#
+    )?;
+    for x in 0..matrix.size_x() {
+        for (y, color) in matrix.row_iter(x).enumerate() {
+            writeln!(
+                out,
+                r#"<rect x="{}" y="{}" width="{scale}" height="{scale}" fill="{}"/>"#,
+                y * scale,
+                x * scale,
+                color.hex()
+            )?;
+        }
+    }
+    writeln!(out, "</svg>")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::matrix::DynMatrix;
+
+    fn test_image() -> DynMatrix<Rgb> {
+        DynMatrix::from(vec![vec![Rgb::BLACK, Rgb(1, 2, 3)]])
+    }
+
+    #[test]
+    fn test_ppm() {
+        let mut out = Vec::new();
+        write_ppm(&test_image(), 2, &mut out).unwrap();
+        let header = b"P6\n4 2\n255\n";
+        assert_eq!(&out[..header.len()], header);
+        let row = [0, 0, 0, 0, 0, 0, 1, 2, 3, 1, 2, 3];
+        assert_eq!(&out[header.len()..], [row, row].concat());
+    }
+
+    #[test]
+    fn test_pbm() {
+        let image = DynMatrix::from(vec![vec![true, false, true, true, false]]);
+        let mut out = Vec::new();
+        write_pbm(&image, 2, &mut out).unwrap();
+        let header = b"P4\n10 2\n";
+        assert_eq!(&out[..header.len()], header);
+        let row = [0b1100_1111, 0b0000_0000];
+        assert_eq!(&out[header.len()..], [row, row].concat());
+    }
+
+    #[test]
+    fn test_svg() {
+        let mut out = Vec::new();
+        write_svg(&test_image(), 3, &mut out).unwrap();
+        let svg = String::from_utf8(out).unwrap();
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.contains(r#"width="6" height="3""#));
+        assert_eq!(svg.matches("<rect ").count(), 2);
+        assert!(svg.contains(r##"<rect x="3" y="0" width="3" height="3" fill="#010203"/>"##));
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+
+    #[test]
+    fn test_lerp() {
+        assert_eq!(Rgb::BLACK.lerp(Rgb::WHITE, 0.5), Rgb(128, 128, 128));
+        assert_eq!(Rgb::BLACK.lerp(Rgb::WHITE, 2.0), Rgb::WHITE);
+        assert_eq!(Rgb(255, 0, 16).hex(), "#ff0010");
+    }
+}
@@ -1,30 +1,410 @@
+pub mod region;
+pub mod search;
+mod sparse;
+
+use itertools::Itertools;
+
+use crate::*;
+
+pub use sparse::{Bounds, SparseMatrix};
+
+/// A fixed size matrix of N rows with M cells each
+///
+/// X selects the row and Y the column within it, same as for [`DynMatrix`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct StaticMatrix<const N: usize, const M: usize, T> {
-    data: [[T; N]; M],
+    data: [[T; M]; N],
+}
+
+impl<const N: usize, const M: usize, T> From<[[T; M]; N]> for StaticMatrix<N, M, T> {
+    fn from(data: [[T; M]; N]) -> Self {
+        Self { data }
+    }
+}
+
+impl<const N: usize, const M: usize, T: Default> Default for StaticMatrix<N, M, T> {
+    fn default() -> Self {
+        Self::from_fn(|_, _| T::default())
+    }
 }
 
+impl<const N: usize, const M: usize, T: Clone> StaticMatrix<N, M, T> {
+    /// Creates a matrix with every cell set to the given value
+    pub fn filled(value: T) -> Self {
+        Self::from_fn(|_, _| value.clone())
+    }
+
+    /// Swaps the X and Y axes
+    pub fn transpose(&self) -> StaticMatrix<M, N, T> {
+        StaticMatrix::from_fn(|x, y| self.data[y][x].clone())
+    }
+
+    /// Rotates the matrix by 90 degrees clockwise
+    pub fn rotate_cw(&self) -> StaticMatrix<M, N, T> {
+        StaticMatrix::from_fn(|x, y| self.data[N - 1 - y][x].clone())
+    }
+
+    /// Rotates the matrix by 90 degrees counter-clockwise
+    pub fn rotate_ccw(&self) -> StaticMatrix<M, N, T> {
+        StaticMatrix::from_fn(|x, y| self.data[y][M - 1 - x].clone())
+    }
+
+    /// Rotates the matrix by 180 degrees
+    pub fn rotate_180(&self) -> Self {
+        Self::from_fn(|x, y| self.data[N - 1 - x][M - 1 - y].clone())
+    }
+
+    /// Mirrors the matrix left to right, reversing the order of Y
+    pub fn flip_horizontal(&self) -> Self {
+        Self::from_fn(|x, y| self.data[x][M - 1 - y].clone())
+    }
+
+    /// Mirrors the matrix top to bottom, reversing the order of X
+    pub fn flip_vertical(&self) -> Self {
+        Self::from_fn(|x, y| self.data[N - 1 - x][y].clone())
+    }
+}
+
+impl<const N: usize, const M: usize, T> StaticMatrix<N, M, T> {
+    /// Creates a matrix, computing each cell from its position
+    pub fn from_fn<F>(mut cell: F) -> Self
+    where
+        F: FnMut(usize, usize) -> T,
+    {
+        Self {
+            data: std::array::from_fn(|x| std::array::from_fn(|y| cell(x, y))),
+        }
+    }
+
+    /// SAFETY
+    ///
+    /// Panics when X is out of bounds
+    pub fn row(&self, x: usize) -> &[T; M] {
+        &self.data[x]
+    }
+
+    /// SAFETY
+    ///
+    /// Panics when X is out of bounds
+    pub fn row_mut(&mut self, x: usize) -> &mut [T; M] {
+        &mut self.data[x]
+    }
+
+    /// Returns all rows in order of X
+    pub fn rows(&self) -> std::slice::Iter<'_, [T; M]> {
+        self.data.iter()
+    }
+
+    pub fn into_inner(self) -> [[T; M]; N] {
+        self.data
+    }
+}
+
+impl<const N: usize, const M: usize, T> std::ops::Index<(usize, usize)> for StaticMatrix<N, M, T> {
+    type Output = T;
+
+    fn index(&self, (x, y): (usize, usize)) -> &Self::Output {
+        &self.data[x][y]
+    }
+}
+
+impl<const N: usize, const M: usize, T> std::ops::IndexMut<(usize, usize)>
+    for StaticMatrix<N, M, T>
+{
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut Self::Output {
+        &mut self.data[x][y]
+    }
+}
+
+/// Renders the matrix like [`DynMatrix`] does
+impl<const N: usize, const M: usize, T: std::fmt::Display> std::fmt::Display
+    for StaticMatrix<N, M, T>
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_grid(self, f)
+    }
+}
+
+impl<const N: usize, const M: usize, T: std::fmt::Display> std::fmt::Debug
+    for StaticMatrix<N, M, T>
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        debug_grid("StaticMatrix", self, f)
+    }
+}
+
+/// A dynamically sized matrix, stored contiguously in row-major order
+///
+/// X selects the row and Y the column within it
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct DynMatrix<T> {
-    data: Vec<Vec<T>>,
+    data: Vec<T>,
+    size_x: usize,
+    size_y: usize,
 }
 
+/// SAFETY
+///
+/// Panics when the rows are not all of the same length
 impl<T> From<Vec<Vec<T>>> for DynMatrix<T> {
     fn from(data: Vec<Vec<T>>) -> Self {
-        Self { data }
+        let size_x = data.len();
+        let size_y = data.first().map(|row| row.len()).unwrap_or(0);
+        assert!(
+            data.iter().all(|row| row.len() == size_y),
+            "matrix rows must all have the same length"
+        );
+        Self {
+            data: data.into_iter().flatten().collect(),
+            size_x,
+            size_y,
+        }
+    }
+}
+
+impl<T> Default for DynMatrix<T> {
+    fn default() -> Self {
+        Self {
+            data: Vec::new(),
+            size_x: 0,
+            size_y: 0,
+        }
+    }
+}
+
+impl<T: Clone> DynMatrix<T> {
+    /// Creates a matrix of the given size with every cell set to the given value
+    pub fn filled(size_x: usize, size_y: usize, value: T) -> Self {
+        Self {
+            data: vec![value; size_x * size_y],
+            size_x,
+            size_y,
+        }
+    }
+
+    /// Swaps the X and Y axes
+    pub fn transpose(&self) -> Self {
+        Self::from_fn(self.size_y, self.size_x, |x, y| self[(y, x)].clone())
+    }
+
+    /// Rotates the matrix by 90 degrees clockwise
+    pub fn rotate_cw(&self) -> Self {
+        Self::from_fn(self.size_y, self.size_x, |x, y| {
+            self[(self.size_x - 1 - y, x)].clone()
+        })
+    }
+
+    /// Rotates the matrix by 90 degrees counter-clockwise
+    pub fn rotate_ccw(&self) -> Self {
+        Self::from_fn(self.size_y, self.size_x, |x, y| {
+            self[(y, self.size_y - 1 - x)].clone()
+        })
+    }
+
+    /// Rotates the matrix by 180 degrees
+    pub fn rotate_180(&self) -> Self {
+        Self::from_fn(self.size_x, self.size_y, |x, y| {
+            self[(self.size_x - 1 - x, self.size_y - 1 - y)].clone()
+        })
+    }
+
+    /// Mirrors the matrix left to right, reversing the order of Y
+    pub fn flip_horizontal(&self) -> Self {
+        Self::from_fn(self.size_x, self.size_y, |x, y| {
+            self[(x, self.size_y - 1 - y)].clone()
+        })
+    }
+
+    /// Mirrors the matrix top to bottom, reversing the order of X
+    pub fn flip_vertical(&self) -> Self {
+        Self::from_fn(self.size_x, self.size_y, |x, y| {
+            self[(self.size_x - 1 - x, y)].clone()
+        })
+    }
+}
+
+impl<T> DynMatrix<T> {
+    /// Creates a matrix of the given size, computing each cell from its position
+    pub fn from_fn<F>(size_x: usize, size_y: usize, mut cell: F) -> Self
+    where
+        F: FnMut(usize, usize) -> T,
+    {
+        let data = (0..size_x)
+            .flat_map(|x| (0..size_y).map(move |y| (x, y)))
+            .map(|(x, y)| cell(x, y))
+            .collect();
+        Self {
+            data,
+            size_x,
+            size_y,
+        }
+    }
+
+    /// Parses a rectangular grid of characters, one row per line,
+    /// converting each character into a cell with the given closure
+    pub fn from_grid<S, I, F>(lines: I, mut cell: F) -> Result<Self>
+    where
+        S: AsRef<str>,
+        I: IntoIterator<Item = S>,
+        F: FnMut(char) -> Result<T>,
+    {
+        let mut data: Vec<T> = Vec::new();
+        let mut size_x = 0;
+        let mut size_y = None;
+        for (x, line) in lines.into_iter().enumerate() {
+            let row_start = data.len();
+            for c in line.as_ref().chars() {
+                data.push(cell(c).with_context(|| format!("parsing grid row {x}"))?);
+            }
+            let row_len = data.len() - row_start;
+            match size_y {
+                None => size_y = Some(row_len),
+                Some(size_y) if size_y != row_len => {
+                    return Err(report!(
+                        "grid row {x} has {row_len} cells, expected {size_y}"
+                    ));
+                }
+                Some(_) => (),
+            }
+            size_x += 1;
+        }
+        Ok(Self {
+            data,
+            size_x,
+            size_y: size_y.unwrap_or(0),
+        })
+    }
+
+    /// Parses a grid from a multi-line string, see [`DynMatrix::from_grid`]
+    pub fn from_grid_str<F>(s: &str, cell: F) -> Result<Self>
+    where
+        F: FnMut(char) -> Result<T>,
+    {
+        Self::from_grid(s.lines(), cell)
+    }
+
+    /// Creates a matrix of the same size by converting every cell
+    pub fn map<U, F>(&self, cell: F) -> DynMatrix<U>
+    where
+        F: FnMut(&T) -> U,
+    {
+        DynMatrix {
+            data: self.data.iter().map(cell).collect(),
+            size_x: self.size_x,
+            size_y: self.size_y,
+        }
+    }
+
+    /// Returns true if the matrix has no cells
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// SAFETY
+    ///
+    /// Panics when X is out of bounds
+    pub fn row(&self, x: usize) -> &[T] {
+        assert!(x < self.size_x, "row {x} out of bounds");
+        &self.data[x * self.size_y..(x + 1) * self.size_y]
+    }
+
+    /// SAFETY
+    ///
+    /// Panics when X is out of bounds
+    pub fn row_mut(&mut self, x: usize) -> &mut [T] {
+        assert!(x < self.size_x, "row {x} out of bounds");
+        &mut self.data[x * self.size_y..(x + 1) * self.size_y]
+    }
+
+    /// Returns all rows in order of X
+    pub fn rows(&self) -> impl DoubleEndedIterator<Item = &[T]> + ExactSizeIterator {
+        (0..self.size_x).map(|x| self.row(x))
+    }
+
+    /// Returns all cells in row-major order
+    pub fn cells(&self) -> std::slice::Iter<'_, T> {
+        self.data.iter()
+    }
+
+    /// Returns all cells in row-major order
+    pub fn cells_mut(&mut self) -> std::slice::IterMut<'_, T> {
+        self.data.iter_mut()
+    }
+
+    fn index_of(&self, x: usize, y: usize) -> Option<usize> {
+        self.in_bounds(x, y).then_some(x * self.size_y + y)
+    }
+}
+
+impl<T> std::ops::Index<(usize, usize)> for DynMatrix<T> {
+    type Output = T;
+
+    fn index(&self, (x, y): (usize, usize)) -> &Self::Output {
+        match self.index_of(x, y) {
+            Some(idx) => &self.data[idx],
+            None => panic!(
+                "position {x}/{y} out of bounds for {}x{} matrix",
+                self.size_x, self.size_y
+            ),
+        }
+    }
+}
+
+impl<T> std::ops::IndexMut<(usize, usize)> for DynMatrix<T> {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut Self::Output {
+        match self.index_of(x, y) {
+            Some(idx) => &mut self.data[idx],
+            None => panic!(
+                "position {x}/{y} out of bounds for {}x{} matrix",
+                self.size_x, self.size_y
+            ),
+        }
+    }
+}
+
+/// Renders the matrix row by row with the cells written back to back,
+/// so single-character cells round-trip through [`DynMatrix::from_grid_str`]
+impl<T: std::fmt::Display> std::fmt::Display for DynMatrix<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_grid(self, f)
     }
 }
 
 impl<T: std::fmt::Display> std::fmt::Debug for DynMatrix<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut ds = f.debug_struct("DynMatrix");
-        for (iidx, idat) in self.data.iter().enumerate() {
-            let mut idatbuf = Vec::new();
-            for jdat in idat {
-                idatbuf.push(jdat.to_string());
-            }
-            let idatbuf = idatbuf.join(" ");
-            ds.field(&iidx.to_string(), &idatbuf);
+        debug_grid("DynMatrix", self, f)
+    }
+}
+
+fn fmt_grid<M>(matrix: &M, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+where
+    M: Matrix + ?Sized,
+    M::Data: std::fmt::Display,
+{
+    for x in 0..matrix.size_x() {
+        if x > 0 {
+            f.write_str("\n")?;
+        }
+        for cell in matrix.row_iter(x) {
+            write!(f, "{cell}")?;
         }
-        ds.finish()
     }
+    Ok(())
+}
+
+fn debug_grid<M>(name: &str, matrix: &M, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+where
+    M: Matrix + ?Sized,
+    M::Data: std::fmt::Display,
+{
+    let rows = (0..matrix.size_x())
+        .map(|x| matrix.row_iter(x).map(|cell| cell.to_string()).join(" "))
+        .collect_vec();
+    f.debug_struct(name)
+        .field("size_x", &matrix.size_x())
+        .field("size_y", &matrix.size_y())
+        .field("rows", &rows)
+        .finish()
 }
 
 pub trait Matrix {
@@ -37,14 +417,400 @@ pub trait Matrix {
     ///
     /// Panics when X or Y are out of bounds
     fn get(&self, x: usize, y: usize) -> Option<&Self::Data>;
-    /// SAFETY
-    ///
-    /// panics when X or Y are out of bounds
-    fn set(&mut self, x: usize, y: usize, data: Self::Data) -> Option<Self::Data>;
     fn size_x(&self) -> usize;
     fn size_y(&self) -> usize;
+
+    /// Borrows the rectangle of the given size starting at X and Y as its own matrix
+    ///
+    /// SAFETY
+    ///
+    /// Panics when the rectangle does not fit into the matrix
+    fn view(&self, x: usize, y: usize, size_x: usize, size_y: usize) -> MatrixView<'_, Self> {
+        check_view(self, x, y, size_x, size_y);
+        MatrixView {
+            matrix: self,
+            x,
+            y,
+            size_x,
+            size_y,
+        }
+    }
+    /// Copies the cells into a new [`DynMatrix`], for example to transform a view
+    fn to_dyn_matrix(&self) -> DynMatrix<Self::Data>
+    where
+        Self::Data: Clone,
+    {
+        DynMatrix::from_fn(self.size_x(), self.size_y(), |x, y| {
+            self.get(x, y).expect("cell within size").clone()
+        })
+    }
+
+    /// Returns the in-bounds positions orthogonally adjacent to X and Y
+    fn neighbours4(&self, x: usize, y: usize) -> Neighbours<'_, Self> {
+        Neighbours::new(self, x, y, &Direction::CARDINAL)
+    }
+    /// Returns the in-bounds positions orthogonally and diagonally adjacent to X and Y
+    fn neighbours8(&self, x: usize, y: usize) -> Neighbours<'_, Self> {
+        Neighbours::new(self, x, y, &Direction::ALL)
+    }
+    /// Returns the positions from X and Y towards the edge in the given direction,
+    /// excluding the starting position itself
+    fn ray(&self, x: usize, y: usize, dir: Direction) -> Ray<'_, Self> {
+        Ray {
+            matrix: self,
+            x,
+            y,
+            dir,
+        }
+    }
+    /// Renders the matrix as a character grid, one row per line
+    fn render<F>(&self, mut cell: F) -> String
+    where
+        F: FnMut(&Self::Data) -> char,
+    {
+        (0..self.size_x())
+            .map(|x| self.row_iter(x).map(&mut cell).collect::<String>())
+            .join("\n")
+    }
+    /// Returns the cells of row X, ordered by Y
+    fn row_iter(&self, x: usize) -> Line<'_, Self> {
+        Line {
+            matrix: self,
+            fixed: x,
+            pos: 0,
+            end: if x < self.size_x() { self.size_y() } else { 0 },
+            is_row: true,
+        }
+    }
+    /// Returns the cells of column Y, ordered by X
+    fn col_iter(&self, y: usize) -> Line<'_, Self> {
+        Line {
+            matrix: self,
+            fixed: y,
+            pos: 0,
+            end: if y < self.size_y() { self.size_x() } else { 0 },
+            is_row: false,
+        }
+    }
+}
+
+/// A [`Matrix`] whose cells can be modified
+pub trait MatrixMut: Matrix {
+    /// Returns None when X or Y are out of bounds
+    fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut Self::Data>;
+    /// Replaces the cell at X and Y, returns the previous value
+    /// or None if X or Y are out of bounds
+    fn set(&mut self, x: usize, y: usize, data: Self::Data) -> Option<Self::Data> {
+        self.get_mut(x, y).map(|old| std::mem::replace(old, data))
+    }
+    /// Mutably borrows the rectangle of the given size starting at X and Y as its own matrix
+    ///
+    /// SAFETY
+    ///
+    /// Panics when the rectangle does not fit into the matrix
+    fn view_mut(
+        &mut self,
+        x: usize,
+        y: usize,
+        size_x: usize,
+        size_y: usize,
+    ) -> MatrixViewMut<'_, Self> {
+        check_view(self, x, y, size_x, size_y);
+        MatrixViewMut {
+            matrix: self,
+            x,
+            y,
+            size_x,
+            size_y,
+        }
+    }
+}
+
+fn check_view<M: Matrix + ?Sized>(matrix: &M, x: usize, y: usize, size_x: usize, size_y: usize) {
+    assert!(
+        x + size_x <= matrix.size_x() && y + size_y <= matrix.size_y(),
+        "view of {size_x}x{size_y} at {x}/{y} exceeds {}x{} matrix",
+        matrix.size_x(),
+        matrix.size_y()
+    );
+}
+
+/// A borrowed rectangular part of another matrix, see [`Matrix::view`]
+pub struct MatrixView<'a, M: ?Sized> {
+    matrix: &'a M,
+    x: usize,
+    y: usize,
+    size_x: usize,
+    size_y: usize,
+}
+
+impl<'a, M: Matrix + ?Sized> Matrix for MatrixView<'a, M> {
+    type Data = M::Data;
+
+    fn get(&self, x: usize, y: usize) -> Option<&Self::Data> {
+        if !self.in_bounds(x, y) {
+            return None;
+        }
+        self.matrix.get(self.x + x, self.y + y)
+    }
+
+    fn size_x(&self) -> usize {
+        self.size_x
+    }
+
+    fn size_y(&self) -> usize {
+        self.size_y
+    }
+}
+
+impl<'a, M> std::fmt::Display for MatrixView<'a, M>
+where
+    M: Matrix + ?Sized,
+    M::Data: std::fmt::Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_grid(self, f)
+    }
+}
+
+/// A mutably borrowed rectangular part of another matrix, see [`MatrixMut::view_mut`]
+pub struct MatrixViewMut<'a, M: ?Sized> {
+    matrix: &'a mut M,
+    x: usize,
+    y: usize,
+    size_x: usize,
+    size_y: usize,
+}
+
+impl<'a, M: Matrix + ?Sized> Matrix for MatrixViewMut<'a, M> {
+    type Data = M::Data;
+
+    fn get(&self, x: usize, y: usize) -> Option<&Self::Data> {
+        if !self.in_bounds(x, y) {
+            return None;
+        }
+        self.matrix.get(self.x + x, self.y + y)
+    }
+
+    fn size_x(&self) -> usize {
+        self.size_x
+    }
+
+    fn size_y(&self) -> usize {
+        self.size_y
+    }
+}
+
+impl<'a, M: MatrixMut + ?Sized> MatrixMut for MatrixViewMut<'a, M> {
+    fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut Self::Data> {
+        if !self.in_bounds(x, y) {
+            return None;
+        }
+        self.matrix.get_mut(self.x + x, self.y + y)
+    }
+}
+
+/// A direction on a matrix, X grows downwards and Y grows to the right
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+
+impl Direction {
+    /// The four orthogonal directions
+    pub const CARDINAL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+    /// All eight directions, orthogonal first
+    pub const ALL: [Direction; 8] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+        Direction::UpLeft,
+        Direction::UpRight,
+        Direction::DownLeft,
+        Direction::DownRight,
+    ];
+
+    /// Returns the X and Y offset of a single step in this direction
+    pub const fn delta(self) -> (isize, isize) {
+        match self {
+            Direction::Up => (-1, 0),
+            Direction::Down => (1, 0),
+            Direction::Left => (0, -1),
+            Direction::Right => (0, 1),
+            Direction::UpLeft => (-1, -1),
+            Direction::UpRight => (-1, 1),
+            Direction::DownLeft => (1, -1),
+            Direction::DownRight => (1, 1),
+        }
+    }
+
+    pub const fn opposite(self) -> Self {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+            Direction::UpLeft => Direction::DownRight,
+            Direction::UpRight => Direction::DownLeft,
+            Direction::DownLeft => Direction::UpRight,
+            Direction::DownRight => Direction::UpLeft,
+        }
+    }
+
+    /// Steps once from signed X and Y
+    pub fn step_signed(self, x: isize, y: isize) -> (isize, isize) {
+        let (dx, dy) = self.delta();
+        (x + dx, y + dy)
+    }
+
+    /// Steps once from X and Y, returns None if the step would leave the positive quadrant
+    pub fn step(self, x: usize, y: usize) -> Option<(usize, usize)> {
+        let (dx, dy) = self.delta();
+        Some((x.checked_add_signed(dx)?, y.checked_add_signed(dy)?))
+    }
+}
+
+/// Which cells count as adjacent when walking a matrix
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Connectivity {
+    /// Only orthogonal neighbours, see [`Matrix::neighbours4`]
+    Four,
+    /// Orthogonal and diagonal neighbours, see [`Matrix::neighbours8`]
+    Eight,
+}
+
+impl Connectivity {
+    /// Returns the in-bounds neighbours of X and Y
+    pub fn neighbours<M: Matrix + ?Sized>(
+        self,
+        matrix: &M,
+        x: usize,
+        y: usize,
+    ) -> Neighbours<'_, M> {
+        match self {
+            Connectivity::Four => matrix.neighbours4(x, y),
+            Connectivity::Eight => matrix.neighbours8(x, y),
+        }
+    }
+}
+
+/// Iterator over the in-bounds neighbours of a position, see [`Matrix::neighbours4`]
+pub struct Neighbours<'a, M: ?Sized> {
+    matrix: &'a M,
+    x: usize,
+    y: usize,
+    dirs: std::slice::Iter<'static, Direction>,
+}
+
+impl<'a, M: Matrix + ?Sized> Neighbours<'a, M> {
+    fn new(matrix: &'a M, x: usize, y: usize, dirs: &'static [Direction]) -> Self {
+        Self {
+            matrix,
+            x,
+            y,
+            dirs: dirs.iter(),
+        }
+    }
+}
+
+impl<'a, M: Matrix + ?Sized> Iterator for Neighbours<'a, M> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for dir in self.dirs.by_ref() {
+            match dir.step(self.x, self.y) {
+                Some((x, y)) if self.matrix.in_bounds(x, y) => return Some((x, y)),
+                _ => continue,
+            }
+        }
+        None
+    }
+}
+
+/// Iterator walking from a position towards the edge, see [`Matrix::ray`]
+pub struct Ray<'a, M: ?Sized> {
+    matrix: &'a M,
+    x: usize,
+    y: usize,
+    dir: Direction,
+}
+
+impl<'a, M: Matrix + ?Sized> Iterator for Ray<'a, M> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (x, y) = self.dir.step(self.x, self.y)?;
+        if !self.matrix.in_bounds(x, y) {
+            return None;
+        }
+        self.x = x;
+        self.y = y;
+        Some((x, y))
+    }
+}
+
+/// Iterator over the cells of a single row or column, see [`Matrix::row_iter`]
+pub struct Line<'a, M: ?Sized> {
+    matrix: &'a M,
+    fixed: usize,
+    pos: usize,
+    end: usize,
+    is_row: bool,
+}
+
+impl<'a, M: Matrix + ?Sized> Line<'a, M> {
+    fn cell(&self, pos: usize) -> &'a M::Data {
+        let (x, y) = if self.is_row {
+            (self.fixed, pos)
+        } else {
+            (pos, self.fixed)
+        };
+        self.matrix.get(x, y).expect("line position out of bounds")
+    }
+}
+
+impl<'a, M: Matrix + ?Sized> Iterator for Line<'a, M> {
+    type Item = &'a M::Data;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.end {
+            return None;
+        }
+        self.pos += 1;
+        Some(self.cell(self.pos - 1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.pos;
+        (len, Some(len))
+    }
 }
 
+impl<'a, M: Matrix + ?Sized> DoubleEndedIterator for Line<'a, M> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.end {
+            return None;
+        }
+        self.end -= 1;
+        Some(self.cell(self.end))
+    }
+}
+
+impl<'a, M: Matrix + ?Sized> ExactSizeIterator for Line<'a, M> {}
+
 impl<const N: usize, const M: usize, T> Matrix for StaticMatrix<N, M, T> {
     type Data = T;
 
@@ -56,13 +822,6 @@ impl<const N: usize, const M: usize, T> Matrix for StaticMatrix<N, M, T> {
         x < N && y < M
     }
 
-    fn set(&mut self, x: usize, y: usize, data: Self::Data) -> Option<Self::Data> {
-        self.data
-            .get_mut(x)
-            .and_then(|row| row.get_mut(y))
-            .map(|old| std::mem::replace(old, data))
-    }
-
     fn size_x(&self) -> usize {
         N
     }
@@ -72,25 +831,227 @@ impl<const N: usize, const M: usize, T> Matrix for StaticMatrix<N, M, T> {
     }
 }
 
+impl<const N: usize, const M: usize, T> MatrixMut for StaticMatrix<N, M, T> {
+    fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut Self::Data> {
+        self.data.get_mut(x).and_then(|row| row.get_mut(y))
+    }
+}
+
 impl<T> Matrix for DynMatrix<T> {
     type Data = T;
 
     fn get(&self, x: usize, y: usize) -> Option<&Self::Data> {
-        self.data.get(x).and_then(|row| row.get(y))
-    }
-
-    fn set(&mut self, x: usize, y: usize, data: Self::Data) -> Option<Self::Data> {
-        self.data
-            .get_mut(x)
-            .and_then(|row| row.get_mut(y))
-            .map(|old| std::mem::replace(old, data))
+        self.index_of(x, y).map(|idx| &self.data[idx])
     }
 
     fn size_x(&self) -> usize {
-        self.data.len()
+        self.size_x
     }
 
     fn size_y(&self) -> usize {
-        self.data.first().map(|ylen| ylen.len()).unwrap()
+        self.size_y
+    }
+}
+
+impl<T> MatrixMut for DynMatrix<T> {
+    fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut Self::Data> {
+        self.index_of(x, y).map(|idx| &mut self.data[idx])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_matrix() -> DynMatrix<u8> {
+        DynMatrix::from(vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]])
+    }
+
+    #[test]
+    fn test_neighbours() {
+        let m = test_matrix();
+        assert_eq!(
+            m.neighbours4(1, 1).collect_vec(),
+            vec![(0, 1), (2, 1), (1, 0), (1, 2)]
+        );
+        assert_eq!(m.neighbours4(0, 0).collect_vec(), vec![(1, 0), (0, 1)]);
+        assert_eq!(m.neighbours8(1, 1).count(), 8);
+        assert_eq!(
+            m.neighbours8(2, 2).collect_vec(),
+            vec![(1, 2), (2, 1), (1, 1)]
+        );
+    }
+
+    #[test]
+    fn test_rays() {
+        let m = test_matrix();
+        assert_eq!(
+            m.ray(2, 1, Direction::Up).collect_vec(),
+            vec![(1, 1), (0, 1)]
+        );
+        assert_eq!(m.ray(2, 1, Direction::Down).count(), 0);
+        assert_eq!(
+            m.ray(0, 0, Direction::DownRight).collect_vec(),
+            vec![(1, 1), (2, 2)]
+        );
+        assert_eq!(
+            m.ray(1, 2, Direction::Left).collect_vec(),
+            vec![(1, 1), (1, 0)]
+        );
+    }
+
+    #[test]
+    fn test_lines() {
+        let m = test_matrix();
+        assert_eq!(m.row_iter(1).copied().collect_vec(), vec![4, 5, 6]);
+        assert_eq!(m.col_iter(2).rev().copied().collect_vec(), vec![9, 6, 3]);
+        assert_eq!(m.row_iter(3).count(), 0);
+    }
+
+    const DIGIT_GRID: &str = "30373\n25512\n65332\n33549\n35390";
+    const CHAR_GRID: &str = "#..#\n.##.\n#..#";
+
+    fn digit(c: char) -> Result<u8> {
+        c.to_digit(10)
+            .map(|d| d as u8)
+            .ok_or_else(|| report!("not a digit: {c:?}"))
+    }
+
+    #[test]
+    fn test_digit_round_trip() {
+        let m = DynMatrix::from_grid_str(DIGIT_GRID, digit).unwrap();
+        assert_eq!(m.size_x(), 5);
+        assert_eq!(m.size_y(), 5);
+        assert_eq!(m.get(3, 4), Some(&9));
+        assert_eq!(m.to_string(), DIGIT_GRID);
+    }
+
+    #[test]
+    fn test_char_round_trip() {
+        let m = DynMatrix::from_grid_str(CHAR_GRID, |c| Ok(c == '#')).unwrap();
+        assert_eq!(m.size_x(), 3);
+        assert_eq!(m.size_y(), 4);
+        assert_eq!(m.get(1, 1), Some(&true));
+        assert_eq!(m.render(|c| if *c { '#' } else { '.' }), CHAR_GRID);
+    }
+
+    #[test]
+    fn test_flat_storage() {
+        let mut m = test_matrix();
+        assert_eq!(m[(1, 2)], 6);
+        m[(1, 2)] = 10;
+        assert_eq!(m.set(1, 2, 11), Some(10));
+        assert_eq!(m.row(1), &[4, 5, 11]);
+        m.row_mut(2).reverse();
+        assert_eq!(m.rows().last(), Some(&[9, 8, 7][..]));
+        assert_eq!(m.get(0, 3), None);
+        assert_eq!(m.set(3, 0, 1), None);
+        assert_eq!(m, DynMatrix::from_fn(3, 3, |x, y| m[(x, y)]));
+        assert_eq!(m.map(|c| c * 2).row(0), &[2, 4, 6]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_index_out_of_row() {
+        let m = test_matrix();
+        // would alias (1, 0) in the backing store
+        let _ = m[(0, 3)];
+    }
+
+    #[test]
+    fn test_empty_matrix() {
+        let m: DynMatrix<u8> = DynMatrix::from(Vec::new());
+        assert!(m.is_empty());
+        assert_eq!((m.size_x(), m.size_y()), (0, 0));
+        assert_eq!(m.get(0, 0), None);
+        assert_eq!(m.rows().count(), 0);
+        assert_eq!(m.to_string(), "");
+
+        let m = DynMatrix::<char>::from_grid_str("", Ok).unwrap();
+        assert!(m.is_empty());
+        assert_eq!(m.size_y(), 0);
+
+        let m: DynMatrix<u8> = DynMatrix::filled(2, 0, 0);
+        assert_eq!(m.rows().map(|row| row.len()).collect_vec(), vec![0, 0]);
+        assert!(!m.in_bounds(1, 0));
+    }
+
+    #[test]
+    fn test_static_axes() {
+        let mut m: StaticMatrix<2, 3, u8> = StaticMatrix::from([[1, 2, 3], [4, 5, 6]]);
+        assert_eq!((m.size_x(), m.size_y()), (2, 3));
+        assert!(m.in_bounds(1, 2));
+        assert!(!m.in_bounds(2, 1));
+        assert_eq!(m.get(1, 2), Some(&6));
+        assert_eq!(m.get(2, 1), None);
+        assert_eq!(m.set(0, 2, 9), Some(3));
+        assert_eq!(m.row_iter(0).copied().collect_vec(), vec![1, 2, 9]);
+        assert_eq!(m.col_iter(1).copied().collect_vec(), vec![2, 5]);
+        assert_eq!(m.to_string(), "129\n456");
+        assert_eq!(StaticMatrix::<2, 2, u8>::filled(7).to_string(), "77\n77");
+        assert_eq!(StaticMatrix::<2, 2, u8>::default(), StaticMatrix::filled(0));
+    }
+
+    #[test]
+    fn test_static_transforms() {
+        let m: StaticMatrix<2, 3, u8> = StaticMatrix::from_fn(|x, y| (x * 3 + y) as u8);
+        assert_eq!(m.to_string(), "012\n345");
+        assert_eq!(m.transpose().to_string(), "03\n14\n25");
+        assert_eq!(m.rotate_cw().to_string(), "30\n41\n52");
+        assert_eq!(m.rotate_ccw().to_string(), "25\n14\n03");
+        assert_eq!(m.rotate_180().to_string(), "543\n210");
+        assert_eq!(m.flip_horizontal().to_string(), "210\n543");
+        assert_eq!(m.flip_vertical().to_string(), "345\n012");
+        assert_eq!(m.rotate_cw().rotate_cw(), m.rotate_180());
+        assert_eq!(m.rotate_cw().rotate_ccw(), m);
+        assert_eq!(m.transpose().transpose(), m);
+    }
+
+    #[test]
+    fn test_dyn_transforms() {
+        let m = DynMatrix::from_fn(2, 3, |x, y| (x * 3 + y) as u8);
+        assert_eq!(m.to_string(), "012\n345");
+        assert_eq!(m.transpose().to_string(), "03\n14\n25");
+        assert_eq!(m.rotate_cw().to_string(), "30\n41\n52");
+        assert_eq!(m.rotate_ccw().to_string(), "25\n14\n03");
+        assert_eq!(m.rotate_180().to_string(), "543\n210");
+        assert_eq!(m.flip_horizontal().to_string(), "210\n543");
+        assert_eq!(m.flip_vertical().to_string(), "345\n012");
+        assert_eq!(m.rotate_cw().rotate_ccw(), m);
+
+        let s: StaticMatrix<2, 3, u8> = StaticMatrix::from_fn(|x, y| m[(x, y)]);
+        assert_eq!(s.rotate_cw().to_dyn_matrix(), m.rotate_cw());
+        assert_eq!(DynMatrix::<u8>::default().rotate_cw(), DynMatrix::default());
+    }
+
+    #[test]
+    fn test_views() {
+        let mut m = test_matrix();
+        let view = m.view(1, 1, 2, 2);
+        assert_eq!(view.to_string(), "56\n89");
+        assert_eq!(view.get(0, 1), Some(&6));
+        assert_eq!(view.get(2, 0), None);
+        assert_eq!(view.neighbours8(0, 0).count(), 3);
+        assert_eq!(view.view(1, 0, 1, 2).to_string(), "89");
+        assert_eq!(view.to_dyn_matrix().rotate_cw().to_string(), "85\n96");
+
+        let mut view = m.view_mut(0, 1, 3, 1);
+        assert_eq!(view.set(2, 0, 0), Some(8));
+        assert_eq!(view.set(0, 1, 0), None);
+        *view.get_mut(0, 0).unwrap() = 0;
+        assert_eq!(m.to_string(), "103\n456\n709");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_view_out_of_bounds() {
+        let m = test_matrix();
+        m.view(2, 2, 2, 1);
+    }
+
+    #[test]
+    fn test_invalid_grids() {
+        assert!(DynMatrix::from_grid_str("123\n45", digit).is_err());
+        assert!(DynMatrix::from_grid_str("123\n4x6", digit).is_err());
     }
 }
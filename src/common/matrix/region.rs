@@ -0,0 +1,213 @@
+//! Region growing over the cells of a [`Matrix`]
+//!
+//! A cell belongs to a region if the predicate holds for it, regions are
+//! formed by adjacent matching cells under the given [`Connectivity`].
+
+use super::{search::Pos, Connectivity, DynMatrix, Matrix};
+
+/// Returns the positions of the region containing the start, in the order they were reached.
+/// The region is empty if the start is out of bounds or does not match the predicate.
+pub fn flood_fill<M, P>(
+    matrix: &M,
+    start: Pos,
+    connectivity: Connectivity,
+    mut belongs: P,
+) -> Vec<Pos>
+where
+    M: Matrix + ?Sized,
+    P: FnMut(&M::Data) -> bool,
+{
+    if !matrix.in_bounds(start.0, start.1) {
+        return Vec::new();
+    }
+    let mut visited = DynMatrix::filled(matrix.size_x(), matrix.size_y(), false);
+    grow(matrix, start, connectivity, |pos| {
+        if visited[pos] {
+            return false;
+        }
+        visited[pos] = true;
+        matrix.get(pos.0, pos.1).is_some_and(&mut belongs)
+    })
+}
+
+/// Grows a region breadth first from the start. `claim` is asked once for the start and
+/// for the neighbours of every claimed cell, and returns whether the cell joins the region.
+/// It must return false for cells it claimed before.
+fn grow<M, C>(matrix: &M, start: Pos, connectivity: Connectivity, mut claim: C) -> Vec<Pos>
+where
+    M: Matrix + ?Sized,
+    C: FnMut(Pos) -> bool,
+{
+    if !claim(start) {
+        return Vec::new();
+    }
+    let mut region = vec![start];
+    let mut next = 0;
+    while let Some(&pos) = region.get(next) {
+        next += 1;
+        for neighbour in connectivity.neighbours(matrix, pos.0, pos.1) {
+            if claim(neighbour) {
+                region.push(neighbour);
+            }
+        }
+    }
+    region
+}
+
+/// Labelled regions of a matrix, see [`connected_components`]
+#[derive(Clone)]
+pub struct Components {
+    labels: DynMatrix<Option<usize>>,
+    sizes: Vec<usize>,
+    /// Cells of each region in row-major order, indexed by label
+    regions: Vec<Vec<Pos>>,
+}
+
+impl Components {
+    /// Returns the label of the region at X and Y, None if the cell belongs to no region
+    pub fn label(&self, x: usize, y: usize) -> Option<usize> {
+        self.labels.get(x, y).copied().flatten()
+    }
+
+    /// Returns the label of every cell
+    pub fn labels(&self) -> &DynMatrix<Option<usize>> {
+        &self.labels
+    }
+
+    /// Returns the number of cells of each region, indexed by label
+    pub fn sizes(&self) -> &[usize] {
+        &self.sizes
+    }
+
+    /// Returns the number of regions
+    pub fn len(&self) -> usize {
+        self.sizes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sizes.is_empty()
+    }
+
+    /// Returns the positions of the region with the given label in row-major order
+    pub fn positions(&self, label: usize) -> impl Iterator<Item = Pos> + '_ {
+        self.regions.get(label).into_iter().flatten().copied()
+    }
+}
+
+/// Splits all cells matching the predicate into connected regions.
+/// Labels count up from 0 in row-major order of each region's first cell.
+pub fn connected_components<M, P>(
+    matrix: &M,
+    connectivity: Connectivity,
+    mut belongs: P,
+) -> Components
+where
+    M: Matrix + ?Sized,
+    P: FnMut(&M::Data) -> bool,
+{
+    let matches = DynMatrix::from_fn(matrix.size_x(), matrix.size_y(), |x, y| {
+        matrix.get(x, y).map(&mut belongs).unwrap_or(false)
+    });
+    let mut labels: DynMatrix<Option<usize>> =
+        DynMatrix::filled(matrix.size_x(), matrix.size_y(), None);
+    let mut sizes = Vec::new();
+    let mut regions = Vec::new();
+    for x in 0..matrix.size_x() {
+        for y in 0..matrix.size_y() {
+            if !matches[(x, y)] || labels[(x, y)].is_some() {
+                continue;
+            }
+            let label = sizes.len();
+            // labels double as the visited set, so every cell is claimed only once overall
+            let mut region = grow(&matches, (x, y), connectivity, |pos| {
+                if !matches[pos] || labels[pos].is_some() {
+                    return false;
+                }
+                labels[pos] = Some(label);
+                true
+            });
+            region.sort_unstable();
+            sizes.push(region.len());
+            regions.push(region);
+        }
+    }
+    Components {
+        labels,
+        sizes,
+        regions,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use itertools::Itertools;
+
+    use super::*;
+    use crate::Result;
+
+    /// The heightmap from 2021 day 9, basins are bounded by height 9
+    const HEIGHTS: &str = "2199943210
+3987894921
+9856789892
+8767896789
+9899965678";
+
+    fn heights() -> DynMatrix<u32> {
+        DynMatrix::from_grid_str(HEIGHTS, |c| -> Result<u32> { Ok(c.to_digit(10).unwrap()) })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_flood_fill() {
+        let m = heights();
+        let basin = flood_fill(&m, (0, 9), Connectivity::Four, |h| *h != 9);
+        assert_eq!(basin.len(), 9);
+        assert_eq!(basin.first(), Some(&(0, 9)));
+        assert!(basin.iter().all(|pos| m[*pos] != 9));
+        assert!(flood_fill(&m, (0, 2), Connectivity::Four, |h| *h != 9).is_empty());
+        assert!(flood_fill(&m, (9, 9), Connectivity::Four, |h| *h != 9).is_empty());
+    }
+
+    #[test]
+    fn test_basins() {
+        let m = heights();
+        let basins = connected_components(&m, Connectivity::Four, |h| *h != 9);
+        assert_eq!(basins.len(), 4);
+        assert_eq!(basins.sizes(), &[3, 9, 14, 9]);
+        assert_eq!(basins.label(0, 0), Some(0));
+        assert_eq!(basins.label(0, 2), None);
+        assert_eq!(basins.label(4, 9), Some(3));
+        assert_eq!(
+            basins.positions(0).collect_vec(),
+            vec![(0, 0), (0, 1), (1, 0)]
+        );
+        for (label, size) in basins.sizes().iter().enumerate() {
+            let positions = basins.positions(label).collect_vec();
+            assert_eq!(positions.len(), *size);
+            assert!(positions.iter().tuple_windows().all(|(a, b)| a < b));
+            assert!(positions
+                .iter()
+                .all(|(x, y)| basins.label(*x, *y) == Some(label)));
+        }
+        assert_eq!(basins.positions(4).count(), 0);
+        let largest: usize = basins.sizes().iter().sorted().rev().take(3).product();
+        assert_eq!(largest, 1134);
+
+        let merged = connected_components(&m, Connectivity::Eight, |h| *h != 9);
+        assert!(merged.len() < basins.len());
+        assert_eq!(merged.sizes().iter().sum::<usize>(), 35);
+    }
+
+    #[test]
+    fn test_checkerboard() {
+        let board = DynMatrix::from_fn(400, 400, |x, y| (x + y) % 2 == 0);
+        let squares = connected_components(&board, Connectivity::Four, |set| *set);
+        assert_eq!(squares.len(), 400 * 400 / 2);
+        assert!(squares.sizes().iter().all(|size| *size == 1));
+        assert_eq!(squares.label(0, 2), Some(1));
+        assert_eq!(squares.label(1, 0), None);
+
+        let diagonals = connected_components(&board, Connectivity::Eight, |set| *set);
+        assert_eq!(diagonals.len(), 1);
+    }
+}
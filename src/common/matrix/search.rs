@@ -0,0 +1,277 @@
+//! Shortest path searches over the cells of a [`Matrix`]
+//!
+//! The closures passed to the searches receive the position being left and the
+//! position being entered, so they can look at both cells to decide whether the
+//! step is possible and what it costs.
+
+use std::{cmp::Reverse, collections::BinaryHeap, collections::VecDeque, ops::Add};
+
+use super::{Connectivity, DynMatrix, Matrix, MatrixMut};
+
+pub type Pos = (usize, usize);
+
+/// Distances from the start of a search to every reached cell,
+/// along with the step each cell was reached from
+#[derive(Clone)]
+pub struct SearchResult<C> {
+    start: Pos,
+    dist: DynMatrix<Option<C>>,
+    prev: DynMatrix<Option<Pos>>,
+}
+
+impl<C: Copy> SearchResult<C> {
+    fn new<M: Matrix + ?Sized>(matrix: &M, start: Pos) -> Self {
+        Self {
+            start,
+            dist: DynMatrix::filled(matrix.size_x(), matrix.size_y(), None),
+            prev: DynMatrix::filled(matrix.size_x(), matrix.size_y(), None),
+        }
+    }
+
+    pub fn start(&self) -> Pos {
+        self.start
+    }
+
+    /// Returns the distance to X and Y, None if the cell was not reached
+    pub fn distance(&self, x: usize, y: usize) -> Option<C> {
+        self.dist.get(x, y).copied().flatten()
+    }
+
+    /// Returns the distance to every cell, None for cells that were not reached
+    pub fn distances(&self) -> &DynMatrix<Option<C>> {
+        &self.dist
+    }
+
+    /// Returns the positions from the start up to and including X and Y,
+    /// None if the cell was not reached
+    pub fn path_to(&self, x: usize, y: usize) -> Option<Vec<Pos>> {
+        self.distance(x, y)?;
+        let mut path = vec![(x, y)];
+        while let Some(prev) = self.prev[*path.last().unwrap()] {
+            path.push(prev);
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Returns the number of reached cells, including the start
+    pub fn reached(&self) -> usize {
+        self.dist.cells().filter(|d| d.is_some()).count()
+    }
+}
+
+/// Finds the fewest number of steps from the start to every cell
+///
+/// `passable(from, to)` decides whether a step between neighbouring cells is allowed
+pub fn bfs<M, F>(
+    matrix: &M,
+    start: Pos,
+    connectivity: Connectivity,
+    mut passable: F,
+) -> SearchResult<usize>
+where
+    M: Matrix + ?Sized,
+    F: FnMut(Pos, Pos) -> bool,
+{
+    let mut result = SearchResult::new(matrix, start);
+    if !matrix.in_bounds(start.0, start.1) {
+        return result;
+    }
+    result.dist.set(start.0, start.1, Some(0));
+    let mut queue = VecDeque::from([(start, 0)]);
+    while let Some((pos, dist)) = queue.pop_front() {
+        for next in connectivity.neighbours(matrix, pos.0, pos.1) {
+            if result.dist[next].is_some() || !passable(pos, next) {
+                continue;
+            }
+            result.dist[next] = Some(dist + 1);
+            result.prev[next] = Some(pos);
+            queue.push_back((next, dist + 1));
+        }
+    }
+    result
+}
+
+/// Finds the cheapest path from the start to every cell
+///
+/// `cost(from, to)` returns the cost of a step between neighbouring cells
+/// or None if the step is not allowed. `C::default()` must be the zero cost.
+pub fn dijkstra<M, C, F>(
+    matrix: &M,
+    start: Pos,
+    connectivity: Connectivity,
+    mut cost: F,
+) -> SearchResult<C>
+where
+    M: Matrix + ?Sized,
+    C: Copy + Ord + Default + Add<Output = C>,
+    F: FnMut(Pos, Pos) -> Option<C>,
+{
+    let mut result = SearchResult::new(matrix, start);
+    if !matrix.in_bounds(start.0, start.1) {
+        return result;
+    }
+    result.dist.set(start.0, start.1, Some(C::default()));
+    let mut heap = BinaryHeap::from([Reverse((C::default(), start))]);
+    while let Some(Reverse((dist, pos))) = heap.pop() {
+        if result.dist[pos].map(|best| dist > best).unwrap_or(false) {
+            continue;
+        }
+        for next in connectivity.neighbours(matrix, pos.0, pos.1) {
+            let Some(step) = cost(pos, next) else {
+                continue;
+            };
+            let next_dist = dist + step;
+            if result.dist[next]
+                .map(|best| next_dist < best)
+                .unwrap_or(true)
+            {
+                result.dist[next] = Some(next_dist);
+                result.prev[next] = Some(pos);
+                heap.push(Reverse((next_dist, next)));
+            }
+        }
+    }
+    result
+}
+
+/// Finds the cheapest path from the start to the goal, returning its cost and the
+/// positions along it including start and goal
+///
+/// `cost` works like for [`dijkstra`]. `heuristic(pos)` estimates the remaining cost
+/// to the goal and must never overestimate it for the result to be the cheapest path.
+pub fn astar<M, C, F, H>(
+    matrix: &M,
+    start: Pos,
+    goal: Pos,
+    connectivity: Connectivity,
+    mut cost: F,
+    mut heuristic: H,
+) -> Option<(C, Vec<Pos>)>
+where
+    M: Matrix + ?Sized,
+    C: Copy + Ord + Default + Add<Output = C>,
+    F: FnMut(Pos, Pos) -> Option<C>,
+    H: FnMut(Pos) -> C,
+{
+    let mut result = SearchResult::new(matrix, start);
+    if !matrix.in_bounds(start.0, start.1) || !matrix.in_bounds(goal.0, goal.1) {
+        return None;
+    }
+    result.dist.set(start.0, start.1, Some(C::default()));
+    let mut heap = BinaryHeap::from([Reverse((heuristic(start), C::default(), start))]);
+    while let Some(Reverse((_, dist, pos))) = heap.pop() {
+        if pos == goal {
+            return Some((dist, result.path_to(goal.0, goal.1)?));
+        }
+        if result.dist[pos].map(|best| dist > best).unwrap_or(false) {
+            continue;
+        }
+        for next in connectivity.neighbours(matrix, pos.0, pos.1) {
+            let Some(step) = cost(pos, next) else {
+                continue;
+            };
+            let next_dist = dist + step;
+            if result.dist[next]
+                .map(|best| next_dist < best)
+                .unwrap_or(true)
+            {
+                result.dist[next] = Some(next_dist);
+                result.prev[next] = Some(pos);
+                heap.push(Reverse((next_dist + heuristic(next), next_dist, next)));
+            }
+        }
+    }
+    None
+}
+
+/// Manhattan distance between two positions, an admissible [`astar`] heuristic
+/// for 4-connected searches where every step costs at least 1
+pub fn manhattan(a: Pos, b: Pos) -> usize {
+    a.0.abs_diff(b.0) + a.1.abs_diff(b.1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Result;
+
+    const MAZE: &str = "..#....\n.##.##.\n....#..\n.####.#\n......#";
+
+    const RISK: &str = "1163751742
+1381373672
+2136511328
+3694931569
+7463417111
+1319128137
+1359912421
+3125421639
+1293138521
+2311944581";
+
+    fn maze() -> DynMatrix<bool> {
+        DynMatrix::from_grid_str(MAZE, |c| Ok(c == '#')).unwrap()
+    }
+
+    fn risk() -> DynMatrix<u32> {
+        DynMatrix::from_grid_str(RISK, |c| -> Result<u32> { Ok(c.to_digit(10).unwrap()) }).unwrap()
+    }
+
+    #[test]
+    fn test_bfs() {
+        let m = maze();
+        let res = bfs(&m, (0, 0), Connectivity::Four, |_, to| !m[to]);
+        assert_eq!(res.distance(0, 0), Some(0));
+        assert_eq!(res.distance(4, 5), Some(9));
+        assert_eq!(res.distance(0, 6), Some(10));
+        assert_eq!(res.distance(0, 2), None);
+        assert_eq!(res.path_to(0, 2), None);
+        let path = res.path_to(4, 5).unwrap();
+        assert_eq!(path.len(), 10);
+        assert_eq!(path.first(), Some(&(0, 0)));
+        assert_eq!(path.last(), Some(&(4, 5)));
+        assert!(path.iter().all(|pos| !m[*pos]));
+        assert!(path.windows(2).all(|w| manhattan(w[0], w[1]) == 1));
+        assert_eq!(res.reached(), m.cells().filter(|wall| !**wall).count());
+
+        let res = bfs(&m, (0, 0), Connectivity::Eight, |_, to| !m[to]);
+        assert_eq!(res.distance(4, 5), Some(8));
+    }
+
+    #[test]
+    fn test_dijkstra() {
+        let m = risk();
+        let res = dijkstra(&m, (0, 0), Connectivity::Four, |_, to| Some(m[to]));
+        assert_eq!(res.distance(9, 9), Some(40));
+        let path = res.path_to(9, 9).unwrap();
+        assert_eq!(path.iter().skip(1).map(|pos| m[*pos]).sum::<u32>(), 40);
+    }
+
+    #[test]
+    fn test_astar() {
+        let m = risk();
+        let (cost, path) = astar(
+            &m,
+            (0, 0),
+            (9, 9),
+            Connectivity::Four,
+            |_, to| Some(m[to]),
+            |pos| manhattan(pos, (9, 9)) as u32,
+        )
+        .unwrap();
+        assert_eq!(cost, 40);
+        assert_eq!(path.first(), Some(&(0, 0)));
+        assert_eq!(path.last(), Some(&(9, 9)));
+
+        let m = maze();
+        let unreachable = astar(
+            &m,
+            (0, 0),
+            (0, 2),
+            Connectivity::Four,
+            |_, to| (!m[to]).then_some(1),
+            |pos| manhattan(pos, (0, 2)),
+        );
+        assert_eq!(unreachable, None);
+    }
+}
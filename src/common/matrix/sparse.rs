@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+
+use super::Direction;
+
+/// The smallest rectangle containing all cells of a [`SparseMatrix`], bounds are inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Bounds {
+    pub min_x: isize,
+    pub max_x: isize,
+    pub min_y: isize,
+    pub max_y: isize,
+}
+
+impl Bounds {
+    fn at(x: isize, y: isize) -> Self {
+        Self {
+            min_x: x,
+            max_x: x,
+            min_y: y,
+            max_y: y,
+        }
+    }
+
+    fn extend(&mut self, x: isize, y: isize) {
+        self.min_x = self.min_x.min(x);
+        self.max_x = self.max_x.max(x);
+        self.min_y = self.min_y.min(y);
+        self.max_y = self.max_y.max(y);
+    }
+
+    /// Returns true if X and Y lie within the bounds
+    pub fn contains(&self, x: isize, y: isize) -> bool {
+        (self.min_x..=self.max_x).contains(&x) && (self.min_y..=self.max_y).contains(&y)
+    }
+
+    fn on_edge(&self, x: isize, y: isize) -> bool {
+        x == self.min_x || x == self.max_x || y == self.min_y || y == self.max_y
+    }
+
+    pub fn size_x(&self) -> usize {
+        self.max_x.abs_diff(self.min_x) + 1
+    }
+
+    pub fn size_y(&self) -> usize {
+        self.max_y.abs_diff(self.min_y) + 1
+    }
+}
+
+/// A grid without fixed bounds that only stores the cells that were set
+///
+/// Positions are signed, X grows downwards and Y grows to the right like on a [`super::Matrix`]
+#[derive(Clone, PartialEq, Eq)]
+pub struct SparseMatrix<T> {
+    cells: HashMap<(isize, isize), T>,
+    bounds: Option<Bounds>,
+}
+
+impl<T> Default for SparseMatrix<T> {
+    fn default() -> Self {
+        Self {
+            cells: HashMap::new(),
+            bounds: None,
+        }
+    }
+}
+
+impl<T> SparseMatrix<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the bounding box of all set cells, None if the matrix is empty
+    pub fn bounds(&self) -> Option<Bounds> {
+        self.bounds
+    }
+
+    /// Returns true if X and Y lie within the bounding box
+    pub fn in_bounds(&self, x: isize, y: isize) -> bool {
+        self.bounds.map(|b| b.contains(x, y)).unwrap_or(false)
+    }
+
+    /// Height of the bounding box
+    pub fn size_x(&self) -> usize {
+        self.bounds.map(|b| b.size_x()).unwrap_or(0)
+    }
+
+    /// Width of the bounding box
+    pub fn size_y(&self) -> usize {
+        self.bounds.map(|b| b.size_y()).unwrap_or(0)
+    }
+
+    /// Number of set cells
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn contains(&self, x: isize, y: isize) -> bool {
+        self.cells.contains_key(&(x, y))
+    }
+
+    pub fn get(&self, x: isize, y: isize) -> Option<&T> {
+        self.cells.get(&(x, y))
+    }
+
+    pub fn get_mut(&mut self, x: isize, y: isize) -> Option<&mut T> {
+        self.cells.get_mut(&(x, y))
+    }
+
+    /// Sets the cell at X and Y, growing the bounds as needed.
+    /// Returns the previous value of the cell if it was set.
+    pub fn set(&mut self, x: isize, y: isize, data: T) -> Option<T> {
+        match self.bounds.as_mut() {
+            None => self.bounds = Some(Bounds::at(x, y)),
+            Some(bounds) => bounds.extend(x, y),
+        }
+        self.cells.insert((x, y), data)
+    }
+
+    /// Returns the cell at X and Y, setting it with the given closure first if it is not set
+    pub fn get_or_insert_with<F: FnOnce() -> T>(&mut self, x: isize, y: isize, f: F) -> &mut T {
+        if !self.contains(x, y) {
+            self.set(x, y, f());
+        }
+        self.get_mut(x, y).expect("cell was just set")
+    }
+
+    /// Unsets the cell at X and Y, shrinking the bounds if it was on their edge
+    pub fn remove(&mut self, x: isize, y: isize) -> Option<T> {
+        let old = self.cells.remove(&(x, y))?;
+        if self.bounds.map(|b| b.on_edge(x, y)).unwrap_or(false) {
+            self.bounds = self.cells.keys().fold(None, |bounds, &(x, y)| {
+                let mut bounds = bounds.unwrap_or(Bounds::at(x, y));
+                bounds.extend(x, y);
+                Some(bounds)
+            });
+        }
+        Some(old)
+    }
+
+    /// Returns all set cells in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = ((isize, isize), &T)> {
+        self.cells.iter().map(|(pos, data)| (*pos, data))
+    }
+
+    /// Returns all set positions in no particular order
+    pub fn positions(&self) -> impl Iterator<Item = (isize, isize)> + '_ {
+        self.cells.keys().copied()
+    }
+
+    /// Returns the positions orthogonally adjacent to X and Y, whether set or not
+    pub fn neighbours4(x: isize, y: isize) -> impl Iterator<Item = (isize, isize)> {
+        Direction::CARDINAL
+            .into_iter()
+            .map(move |dir| dir.step_signed(x, y))
+    }
+
+    /// Returns the positions orthogonally and diagonally adjacent to X and Y, whether set or not
+    pub fn neighbours8(x: isize, y: isize) -> impl Iterator<Item = (isize, isize)> {
+        Direction::ALL
+            .into_iter()
+            .map(move |dir| dir.step_signed(x, y))
+    }
+
+    /// Renders the bounding box as a character grid, one row per line,
+    /// the closure receives None for cells that are not set
+    pub fn render<F>(&self, mut cell: F) -> String
+    where
+        F: FnMut(Option<&T>) -> char,
+    {
+        let Some(bounds) = self.bounds else {
+            return String::new();
+        };
+        (bounds.min_x..=bounds.max_x)
+            .map(|x| {
+                (bounds.min_y..=bounds.max_y)
+                    .map(|y| cell(self.get(x, y)))
+                    .collect::<String>()
+            })
+            .join("\n")
+    }
+}
+
+impl<T> FromIterator<((isize, isize), T)> for SparseMatrix<T> {
+    fn from_iter<I: IntoIterator<Item = ((isize, isize), T)>>(iter: I) -> Self {
+        let mut matrix = Self::new();
+        matrix.extend(iter);
+        matrix
+    }
+}
+
+impl<T> Extend<((isize, isize), T)> for SparseMatrix<T> {
+    fn extend<I: IntoIterator<Item = ((isize, isize), T)>>(&mut self, iter: I) {
+        for ((x, y), data) in iter {
+            self.set(x, y, data);
+        }
+    }
+}
+
+/// Renders the bounding box with the cells written back to back and `.` for unset cells
+impl<T: std::fmt::Display> std::fmt::Display for SparseMatrix<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Some(bounds) = self.bounds else {
+            return Ok(());
+        };
+        for x in bounds.min_x..=bounds.max_x {
+            if x > bounds.min_x {
+                f.write_str("\n")?;
+            }
+            for y in bounds.min_y..=bounds.max_y {
+                match self.get(x, y) {
+                    Some(cell) => write!(f, "{cell}")?,
+                    None => f.write_str(".")?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for SparseMatrix<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SparseMatrix")
+            .field("bounds", &self.bounds)
+            .field("len", &self.cells.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bounds_tracking() {
+        let mut m = SparseMatrix::new();
+        assert_eq!(m.bounds(), None);
+        assert_eq!((m.size_x(), m.size_y()), (0, 0));
+
+        assert_eq!(m.set(0, 0, 'a'), None);
+        assert_eq!(m.set(-2, 3, 'b'), None);
+        assert_eq!(m.set(1, -1, 'c'), None);
+        assert_eq!(m.set(0, 0, 'd'), Some('a'));
+        assert_eq!(
+            m.bounds(),
+            Some(Bounds {
+                min_x: -2,
+                max_x: 1,
+                min_y: -1,
+                max_y: 3
+            })
+        );
+        assert_eq!((m.size_x(), m.size_y()), (4, 5));
+        assert!(m.in_bounds(-1, 2));
+        assert!(!m.contains(-1, 2));
+        assert_eq!(m.get(-2, 3), Some(&'b'));
+
+        assert_eq!(m.remove(-2, 3), Some('b'));
+        assert_eq!(m.remove(-2, 3), None);
+        assert_eq!((m.size_x(), m.size_y()), (2, 2));
+        m.remove(0, 0);
+        m.remove(1, -1);
+        assert!(m.is_empty());
+        assert_eq!(m.bounds(), None);
+    }
+
+    #[test]
+    fn test_render() {
+        let m: SparseMatrix<char> = [((-1, -1), '#'), ((1, 2), '#'), ((0, 0), 's')]
+            .into_iter()
+            .collect();
+        assert_eq!(m.to_string(), "#...\n.s..\n...#");
+        assert_eq!(
+            m.render(|c| if c.is_some() { '#' } else { ' ' }),
+            "#   \n #  \n   #"
+        );
+        assert_eq!(SparseMatrix::<char>::new().to_string(), "");
+    }
+
+    #[test]
+    fn test_neighbours() {
+        let mut m = SparseMatrix::new();
+        for (x, y) in SparseMatrix::<()>::neighbours8(0, 0) {
+            *m.get_or_insert_with(x, y, || 0) += 1;
+        }
+        for (x, y) in SparseMatrix::<()>::neighbours4(-5, 0) {
+            *m.get_or_insert_with(x, y, || 0) += 1;
+        }
+        assert_eq!(m.len(), 12);
+        assert_eq!(m.size_x(), 8);
+        assert!(m.iter().all(|(_, count)| *count == 1));
+    }
+}
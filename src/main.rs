@@ -43,7 +43,10 @@ pub async fn tokio_main() -> Result<()> {
 
     let tc = TaskConfig::parse();
 
-    aoc2022::main(tc).await?;
+    match tc.command() {
+        Some(command) => aoc::aoc2022::run_command(command).await?,
+        None => aoc2022::main(tc).await?,
+    }
 
     Ok(())
 }